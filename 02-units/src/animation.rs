@@ -0,0 +1,336 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{
+    asset::{AssetLoader, Error, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::facing::Facing;
+
+pub const IDLE_CLIP: &str = "idle";
+pub const WALK_CLIP: &str = "walk";
+pub const ATTACK_CLIP: &str = "attack";
+pub const HURT_CLIP: &str = "hurt";
+pub const DEATH_CLIP: &str = "death";
+
+/// A run of frames along one row of a unit sheet. Rows are counted from the
+/// top of the block drawn for the unit's facing. The sheet's grid comes from
+/// the class definition.
+#[derive(Debug, Deserialize)]
+pub struct AnimationClip {
+    pub row: usize,
+    #[serde(default)]
+    pub first_column: usize,
+    pub frames: usize,
+    pub frame_seconds: f32,
+    /// Starts over after the last frame instead of stopping on it.
+    #[serde(default)]
+    pub looping: bool,
+    /// Clip to play once this one finishes, unless another was queued.
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+impl AnimationClip {
+    fn index(&self, frame: usize, columns: usize, first_row: usize) -> usize {
+        (first_row + self.row) * columns + self.first_column + frame
+    }
+}
+
+/// Where the clips of one facing are drawn. Facings without their own
+/// block mirror another one.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct FacingBlock {
+    pub first_row: usize,
+    #[serde(default)]
+    pub flip_x: bool,
+}
+
+/// The named clips of a unit class, keyed by name ("idle", "walk", ...).
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "c4a1f0d6-2b7e-4f35-9e8a-51d2b6c0e7a3"]
+pub struct UnitAnimations {
+    facings: HashMap<Facing, FacingBlock>,
+    clips: HashMap<String, AnimationClip>,
+}
+
+impl UnitAnimations {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let animations: UnitAnimations = ron::de::from_bytes(bytes)?;
+        animations.validate()?;
+
+        Ok(animations)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(facing) = Facing::ALL
+            .into_iter()
+            .find(|facing| !self.facings.contains_key(facing))
+        {
+            return Err(Error::msg(format!("missing the {facing:?} facing")));
+        }
+        if !self.clips.contains_key(IDLE_CLIP) {
+            return Err(Error::msg(format!("missing the '{IDLE_CLIP}' clip")));
+        }
+
+        for (name, clip) in &self.clips {
+            if clip.frames == 0 || clip.frame_seconds <= 0.0 {
+                return Err(Error::msg(format!("clip '{name}' has no frames")));
+            }
+            if let Some(next) = &clip.next {
+                if !self.clips.contains_key(next) {
+                    return Err(Error::msg(format!(
+                        "clip '{name}' chains to unknown clip '{next}'"
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every clip, drawn for every facing, stays on a sheet cut
+    /// into `columns` by `rows` frames.
+    pub fn check_sheet(&self, columns: usize, rows: usize) -> Result<(), Error> {
+        let last_row = self.facings.values().map(|block| block.first_row).max();
+        for (name, clip) in &self.clips {
+            if clip.first_column + clip.frames > columns {
+                return Err(Error::msg(format!(
+                    "clip '{name}' runs past column {columns} of the sheet"
+                )));
+            }
+            if last_row.is_some_and(|first_row| first_row + clip.row >= rows) {
+                return Err(Error::msg(format!(
+                    "clip '{name}' runs past row {rows} of the sheet"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.get(name)
+    }
+
+    pub fn facing(&self, facing: Facing) -> FacingBlock {
+        self.facings[&facing]
+    }
+}
+
+#[derive(Default)]
+pub struct UnitAnimationsLoader;
+
+impl AssetLoader for UnitAnimationsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(UnitAnimations::parse(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
+
+/// The clip a unit is playing and how far into it it is.
+#[derive(Component)]
+pub struct AnimationState {
+    animations: Handle<UnitAnimations>,
+    columns: usize,
+    clip: String,
+    queued: Option<String>,
+    frame: usize,
+    timer: Timer,
+    restart: bool,
+    finished: bool,
+}
+
+impl AnimationState {
+    pub fn new(animations: Handle<UnitAnimations>, columns: usize) -> Self {
+        Self {
+            animations,
+            columns,
+            clip: IDLE_CLIP.to_string(),
+            queued: None,
+            frame: 0,
+            timer: Timer::default(),
+            restart: true,
+            finished: false,
+        }
+    }
+
+    pub fn clip(&self) -> &str {
+        &self.clip
+    }
+
+    /// Whether a non-looping clip has played out and is holding its last
+    /// frame.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Draws the unit with another set of clips and sheet grid, starting its
+    /// current clip over.
+    pub fn set_sheet(&mut self, animations: Handle<UnitAnimations>, columns: usize) {
+        self.animations = animations;
+        self.columns = columns;
+        self.restart = true;
+    }
+
+    /// Switches to a clip right away, dropping anything queued.
+    pub fn play(&mut self, clip: &str) {
+        self.clip = clip.to_string();
+        self.queued = None;
+        self.restart = true;
+    }
+
+    /// Plays a clip after the current one finishes, instead of the clip it
+    /// would chain to. Looping clips finish at the end of their current loop.
+    pub fn queue(&mut self, clip: &str) {
+        self.queued = Some(clip.to_string());
+    }
+
+    /// Moves to the next frame, or to the next clip when the current one runs
+    /// out. Returns false if a non-looping clip is holding its last frame.
+    fn advance(&mut self, animations: &UnitAnimations) -> bool {
+        let Some(clip) = animations.clip(&self.clip) else {
+            return false;
+        };
+
+        if self.frame + 1 < clip.frames {
+            self.frame += 1;
+            return true;
+        }
+
+        if let Some(next) = self.queued.take().or_else(|| clip.next.clone()) {
+            self.clip = next;
+            self.restart = true;
+        } else if clip.looping {
+            self.frame = 0;
+        } else {
+            return false;
+        }
+
+        true
+    }
+}
+
+/// Plays each unit's current clip, following loops and chains, from the
+/// block of the sheet drawn for the way the unit faces.
+pub fn animate_units_system(
+    time: Res<Time>,
+    animations: Res<Assets<UnitAnimations>>,
+    mut units: Query<(
+        &mut AnimationState,
+        &mut TextureAtlasSprite,
+        Option<&Facing>,
+    )>,
+) {
+    for (mut state, mut sprite, facing) in &mut units {
+        let Some(animations) = animations.get(&state.animations) else {
+            continue;
+        };
+
+        if !state.restart {
+            state.timer.tick(time.delta());
+            for _ in 0..state.timer.times_finished_this_tick() {
+                if !state.advance(animations) {
+                    state.finished = true;
+                    break;
+                }
+                if state.restart {
+                    break;
+                }
+            }
+        }
+
+        if state.restart {
+            let Some(clip) = animations.clip(&state.clip) else {
+                warn!("unknown animation clip '{}'", state.clip);
+                state.play(IDLE_CLIP);
+                continue;
+            };
+            let frame_duration = Duration::from_secs_f32(clip.frame_seconds);
+            state.timer = Timer::new(frame_duration, TimerMode::Repeating);
+            state.frame = 0;
+            state.restart = false;
+            state.finished = false;
+        }
+
+        let block = animations.facing(facing.copied().unwrap_or_default());
+        if let Some(clip) = animations.clip(&state.clip) {
+            sprite.index = clip.index(state.frame, state.columns, block.first_row);
+        }
+        if sprite.flip_x != block.flip_x {
+            sprite.flip_x = block.flip_x;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animations(clips: &str) -> UnitAnimations {
+        UnitAnimations::parse(
+            format!(
+                r#"(
+                    facings: {{
+                        Right: (first_row: 0),
+                        Left: (first_row: 0, flip_x: true),
+                        Down: (first_row: 5),
+                        Up: (first_row: 10),
+                    }},
+                    clips: {{ {clips} }},
+                )"#
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn clips_must_fit_on_the_sheet() {
+        let idle = r#""idle": (row: 0, frames: 4, frame_seconds: 0.25, looping: true)"#;
+
+        assert!(animations(idle).check_sheet(8, 14).is_ok());
+        assert!(animations(idle).check_sheet(3, 14).is_err());
+        assert!(animations(&format!(
+            r#"{idle}, "death": (row: 3, first_column: 4, frames: 4, frame_seconds: 0.15)"#
+        ))
+        .check_sheet(8, 14)
+        .is_ok());
+        // Drawn facing up, row 4 of the block is row 14 of the sheet.
+        assert!(animations(&format!(
+            r#"{idle}, "death": (row: 4, frames: 4, frame_seconds: 0.15)"#
+        ))
+        .check_sheet(8, 14)
+        .is_err());
+    }
+
+    #[test]
+    fn clips_must_chain_to_known_clips() {
+        let bytes = br#"(
+            facings: {
+                Right: (first_row: 0),
+                Left: (first_row: 0),
+                Down: (first_row: 0),
+                Up: (first_row: 0),
+            },
+            clips: {
+                "idle": (row: 0, frames: 1, frame_seconds: 0.25, next: Some("rest")),
+            },
+        )"#;
+
+        assert!(UnitAnimations::parse(bytes).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use bevy::{asset::HandleId, prelude::*};
+
+use crate::class::SheetLayout;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct AtlasKey {
+    image: HandleId,
+    /// `SheetLayout::frame_size` as bits, since floats can't be hashed.
+    frame_size: u32,
+    columns: usize,
+    rows: usize,
+}
+
+/// One texture atlas per sprite sheet image and grid, shared by every unit
+/// drawn from that sheet.
+#[derive(Resource, Default)]
+pub struct UnitAtlasCache {
+    atlases: HashMap<AtlasKey, Handle<TextureAtlas>>,
+}
+
+impl UnitAtlasCache {
+    pub fn get_or_load(
+        &mut self,
+        image: &Handle<Image>,
+        layout: SheetLayout,
+        texture_atlases: &mut Assets<TextureAtlas>,
+    ) -> Handle<TextureAtlas> {
+        let key = AtlasKey {
+            image: image.id(),
+            frame_size: layout.frame_size.to_bits(),
+            columns: layout.columns,
+            rows: layout.rows,
+        };
+
+        self.atlases
+            .entry(key)
+            .or_insert_with(|| {
+                texture_atlases.add(TextureAtlas::from_grid(
+                    image.clone(),
+                    Vec2::new(layout.frame_size, layout.frame_size),
+                    layout.columns,
+                    layout.rows,
+                    None,
+                    None,
+                ))
+            })
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.atlases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.atlases.is_empty()
+    }
+}
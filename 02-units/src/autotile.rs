@@ -0,0 +1,330 @@
+//! Picks the grass/dirt transition tiles of `FullTileset.png` from a map
+//! described as plain ground, so designers don't have to choose every edge
+//! piece by hand.
+
+use bevy::asset::Error;
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+
+use crate::tilemap::TileType;
+
+const NORTH: u8 = 1;
+const EAST: u8 = 2;
+const SOUTH: u8 = 4;
+const WEST: u8 = 8;
+
+const DIRT_TILES: [TileType; 4] = [
+    TileType::Brown1,
+    TileType::Brown2,
+    TileType::Brown3,
+    TileType::Brown4,
+];
+const GRASS_TILES: [TileType; 4] = [
+    TileType::Green1,
+    TileType::Green2,
+    TileType::Green3,
+    TileType::Green4,
+];
+const WATER_TILES: [TileType; 5] = [
+    TileType::Water1,
+    TileType::Water2,
+    TileType::Water3,
+    TileType::Water4,
+    TileType::Water5,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ground {
+    Dirt,
+    Grass,
+    Water,
+}
+
+impl Ground {
+    fn from_char(character: char) -> Option<Ground> {
+        match character {
+            'D' => Some(Ground::Dirt),
+            'G' => Some(Ground::Grass),
+            'W' => Some(Ground::Water),
+            _ => None,
+        }
+    }
+}
+
+/// A map written as ground types, one character per tile: `D` for dirt, `G`
+/// for grass and `W` for water. Rows are listed top row first, like `MapAsset::tiles`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GroundLayout {
+    #[serde(default)]
+    pub seed: u64,
+    pub rows: Vec<String>,
+}
+
+impl GroundLayout {
+    pub fn parse(&self) -> Result<Vec<Vec<Ground>>, Error> {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .map(|character| {
+                        Ground::from_char(character).ok_or_else(|| {
+                            Error::msg(format!("unknown ground '{character}' in \"{row}\""))
+                        })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn autotile(&self) -> Result<Vec<Vec<TileType>>, Error> {
+        Ok(autotile(&self.parse()?, self.seed))
+    }
+}
+
+/// Grass tile for a cell whose grass neighbours are given as a bitmask of
+/// `NORTH`, `EAST`, `SOUTH` and `WEST`. The tileset has no piece for a lone
+/// grass tile or for inner corners, so those get a plain grass tile.
+fn grass_edge(mask: u8) -> Option<TileType> {
+    match mask {
+        m if m == EAST | SOUTH => Some(TileType::BrownGreenUpper1),
+        m if m == EAST | SOUTH | WEST => Some(TileType::BrownGreenUpper2),
+        m if m == SOUTH | WEST => Some(TileType::BrownGreenUpper3),
+        SOUTH => Some(TileType::BrownGreenUpper5),
+        m if m == NORTH | SOUTH => Some(TileType::BrownGreenUpper7),
+        m if m == NORTH | EAST | SOUTH => Some(TileType::BrownGreenMiddle1),
+        m if m == NORTH | SOUTH | WEST => Some(TileType::BrownGreenMiddle3),
+        EAST => Some(TileType::BrownGreenMiddle4),
+        WEST => Some(TileType::BrownGreenMiddle6),
+        m if m == NORTH | EAST => Some(TileType::BrownGreenLower1),
+        m if m == NORTH | EAST | WEST => Some(TileType::BrownGreenLower2),
+        m if m == NORTH | WEST => Some(TileType::BrownGreenLower3),
+        NORTH => Some(TileType::BrownGreenLower5),
+        m if m == EAST | WEST => Some(TileType::BrownGreenLower7),
+        _ => None,
+    }
+}
+
+/// Water tile for a cell whose land neighbours are given as a bitmask of
+/// `NORTH`, `EAST`, `SOUTH` and `WEST`, plus the diagonal ones in
+/// `land_corners`. Land on three or four sides has no piece of its own and
+/// falls back to a channel.
+fn water_bank(mask: u8, land_corners: u8) -> Option<TileType> {
+    match mask {
+        0 => match land_corners {
+            m if m == NORTH | EAST => Some(TileType::WaterBankNorthEast),
+            m if m == NORTH | WEST => Some(TileType::WaterBankNorthWest),
+            m if m == SOUTH | EAST => Some(TileType::WaterBankSouthEast),
+            m if m == SOUTH | WEST => Some(TileType::WaterBankSouthWest),
+            _ => None,
+        },
+        NORTH => Some(TileType::WaterBankNorth),
+        EAST => Some(TileType::WaterBankEast),
+        SOUTH => Some(TileType::WaterBankSouth),
+        WEST => Some(TileType::WaterBankWest),
+        m if m == NORTH | EAST => Some(TileType::WaterCornerNorthEast),
+        m if m == NORTH | WEST => Some(TileType::WaterCornerNorthWest),
+        m if m == SOUTH | EAST => Some(TileType::WaterCornerSouthEast),
+        m if m == SOUTH | WEST => Some(TileType::WaterCornerSouthWest),
+        m if m & (EAST | WEST) == EAST | WEST => Some(TileType::WaterChannelVertical),
+        _ => Some(TileType::WaterChannelHorizontal),
+    }
+}
+
+/// A neighbouring cell and the direction bit it sets in a mask. Cells past
+/// the edge of the map are `None`.
+type Neighbour = (u8, Option<usize>, Option<usize>);
+
+fn neighbour_mask(
+    ground: &[Vec<Ground>],
+    cell: Ground,
+    neighbours: [Neighbour; 4],
+    matches: impl Fn(Ground) -> bool,
+) -> u8 {
+    neighbours
+        .into_iter()
+        .filter(|(_, x, y)| {
+            let neighbour = x.zip(*y).and_then(|(x, y)| ground.get(y)?.get(x).copied());
+            matches(neighbour.unwrap_or(cell))
+        })
+        .fold(0, |mask, (direction, _, _)| mask | direction)
+}
+
+/// Turns ground rows, top row first, into tile rows. Cells past the edge of
+/// the map count as the same ground as the cell itself, so the map border
+/// never gets an edge piece. Interior tiles are picked at random from `seed`.
+pub fn autotile(ground: &[Vec<Ground>], seed: u64) -> Vec<Vec<TileType>> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    ground
+        .iter()
+        .enumerate()
+        .map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(x, cell)| {
+                    let interior = match cell {
+                        Ground::Dirt => &DIRT_TILES[..],
+                        Ground::Grass => &GRASS_TILES[..],
+                        Ground::Water => &WATER_TILES[..],
+                    };
+                    let random_interior = *interior.choose(&mut rng).unwrap();
+
+                    let (north, south) = (y.checked_sub(1), y.checked_add(1));
+                    let (east, west) = (x.checked_add(1), x.checked_sub(1));
+                    let sides = [
+                        (NORTH, Some(x), north),
+                        (EAST, east, Some(y)),
+                        (SOUTH, Some(x), south),
+                        (WEST, west, Some(y)),
+                    ];
+                    let corners = [
+                        (NORTH | EAST, east, north),
+                        (NORTH | WEST, west, north),
+                        (SOUTH | EAST, east, south),
+                        (SOUTH | WEST, west, south),
+                    ];
+
+                    let tile = match cell {
+                        Ground::Dirt => None,
+                        Ground::Grass => {
+                            grass_edge(neighbour_mask(ground, *cell, sides, |g| g == Ground::Grass))
+                        }
+                        Ground::Water => {
+                            let is_land = |g| g != Ground::Water;
+                            water_bank(
+                                neighbour_mask(ground, *cell, sides, is_land),
+                                neighbour_mask(ground, *cell, corners, is_land),
+                            )
+                        }
+                    };
+
+                    tile.unwrap_or(random_interior)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::TileType::*;
+
+    fn layout(rows: &[&str], seed: u64) -> Vec<Vec<TileType>> {
+        GroundLayout {
+            seed,
+            rows: rows.iter().map(|row| row.to_string()).collect(),
+        }
+        .autotile()
+        .unwrap()
+    }
+
+    #[test]
+    fn grass_edges_follow_the_grass_around_them() {
+        let cases = [
+            (0, None),
+            (NORTH | EAST | SOUTH | WEST, None),
+            // Outer corners of a patch.
+            (EAST | SOUTH, Some(BrownGreenUpper1)),
+            (SOUTH | WEST, Some(BrownGreenUpper3)),
+            (NORTH | EAST, Some(BrownGreenLower1)),
+            (NORTH | WEST, Some(BrownGreenLower3)),
+            // Straight edges.
+            (EAST | SOUTH | WEST, Some(BrownGreenUpper2)),
+            (NORTH | EAST | SOUTH, Some(BrownGreenMiddle1)),
+            (NORTH | SOUTH | WEST, Some(BrownGreenMiddle3)),
+            (NORTH | EAST | WEST, Some(BrownGreenLower2)),
+            // Tips of one tile wide strips.
+            (SOUTH, Some(BrownGreenUpper5)),
+            (NORTH, Some(BrownGreenLower5)),
+            (EAST, Some(BrownGreenMiddle4)),
+            (WEST, Some(BrownGreenMiddle6)),
+        ];
+
+        for (mask, tile) in cases {
+            assert_eq!(grass_edge(mask), tile, "mask {mask:04b}");
+        }
+    }
+
+    #[test]
+    fn water_banks_follow_the_land_around_them() {
+        let cases = [
+            // Open water.
+            ((0, 0), None),
+            // Straight banks.
+            ((NORTH, 0), Some(WaterBankNorth)),
+            ((EAST, 0), Some(WaterBankEast)),
+            ((SOUTH, 0), Some(WaterBankSouth)),
+            ((WEST, 0), Some(WaterBankWest)),
+            // Outer corners, land on two sides.
+            ((NORTH | EAST, 0), Some(WaterCornerNorthEast)),
+            ((SOUTH | WEST, NORTH | WEST), Some(WaterCornerSouthWest)),
+            // Inner corners, land only diagonally.
+            ((0, NORTH | EAST), Some(WaterBankNorthEast)),
+            ((0, SOUTH | WEST), Some(WaterBankSouthWest)),
+            ((0, NORTH | EAST | SOUTH), None),
+            // Channels, including a lone water tile.
+            ((EAST | WEST, 0), Some(WaterChannelVertical)),
+            ((NORTH | SOUTH, 0), Some(WaterChannelHorizontal)),
+            ((NORTH | EAST | SOUTH | WEST, 0), Some(WaterChannelVertical)),
+        ];
+
+        for ((mask, land_corners), tile) in cases {
+            assert_eq!(
+                water_bank(mask, land_corners),
+                tile,
+                "mask {mask:04b}, corners {land_corners:04b}"
+            );
+        }
+    }
+
+    #[test]
+    fn grass_patch_gets_edges_and_an_interior() {
+        let tiles = layout(&["DDDDD", "DGGGD", "DGGGD", "DGGGD", "DDDDD"], 0);
+
+        assert_eq!(
+            tiles[1][1..4],
+            [BrownGreenUpper1, BrownGreenUpper2, BrownGreenUpper3]
+        );
+        assert_eq!(
+            [tiles[2][1], tiles[2][3]],
+            [BrownGreenMiddle1, BrownGreenMiddle3]
+        );
+        assert_eq!(
+            tiles[3][1..4],
+            [BrownGreenLower1, BrownGreenLower2, BrownGreenLower3]
+        );
+        assert!(GRASS_TILES.contains(&tiles[2][2]));
+        assert!(tiles[0].iter().all(|tile| DIRT_TILES.contains(tile)));
+    }
+
+    #[test]
+    fn lone_tiles_and_map_borders_get_no_edges() {
+        let tiles = layout(&["DDD", "DGD", "DDD"], 0);
+        assert!(GRASS_TILES.contains(&tiles[1][1]));
+
+        // Past the edge counts as more grass.
+        let tiles = layout(&["GG", "GG"], 0);
+        assert!(tiles
+            .iter()
+            .flatten()
+            .all(|tile| GRASS_TILES.contains(tile)));
+    }
+
+    #[test]
+    fn lake_gets_banks_and_corners() {
+        let tiles = layout(&["GGGG", "GWWG", "GWWG", "GGGG"], 0);
+
+        assert_eq!(tiles[1][1..3], [WaterCornerNorthWest, WaterCornerNorthEast]);
+        assert_eq!(tiles[2][1..3], [WaterCornerSouthWest, WaterCornerSouthEast]);
+    }
+
+    #[test]
+    fn interiors_depend_only_on_the_seed() {
+        let rows = ["GGGGGGGG"; 8];
+
+        assert_eq!(layout(&rows, 7), layout(&rows, 7));
+        assert_ne!(layout(&rows, 7), layout(&rows, 8));
+    }
+}
@@ -0,0 +1,288 @@
+use bevy::{asset::Error, prelude::*};
+
+use crate::{
+    grid::GridPos,
+    map::{BattlefieldMap, MapAsset},
+    tilemap::{Tile, Tilemap},
+    tileset::Tileset,
+    water::{WaterAnimation, WaterTile},
+};
+
+const DEFAULT_MAP: &str = "Maps/green_fields.map.ron";
+
+pub const TILE_LAYER: f32 = 0.0;
+pub const UNIT_LAYER: f32 = 1.0;
+
+#[derive(Resource)]
+pub struct Battlefield {
+    tile_size: f32,
+    tilemap: Tilemap,
+}
+
+impl Battlefield {
+    pub fn new(tile_size: f32, tilemap: Tilemap) -> Self {
+        Self { tile_size, tilemap }
+    }
+
+    pub fn from_map(map: &MapAsset, tileset: &Tileset) -> Result<Self, Error> {
+        if map.tile_size != tileset.tile_size {
+            return Err(Error::msg(format!(
+                "map \"{}\" has {}px tiles, but tileset {} has {}px tiles",
+                map.name, map.tile_size, tileset.image, tileset.tile_size
+            )));
+        }
+
+        if let Some(tile_type) = map
+            .tiles
+            .iter()
+            .flatten()
+            .find(|tile_type| !tileset.contains(**tile_type))
+        {
+            return Err(Error::msg(format!(
+                "map \"{}\" uses {tile_type:?}, which is not in tileset {}",
+                map.name, tileset.image
+            )));
+        }
+
+        let tilemap = Tilemap::from_rows(&map.tiles)
+            .ok_or_else(|| Error::msg(format!("map \"{}\" is not rectangular", map.name)))?;
+
+        Ok(Self::new(map.tile_size, tilemap))
+    }
+
+    pub fn tile_size(&self) -> f32 {
+        self.tile_size
+    }
+
+    pub fn tilemap(&self) -> &Tilemap {
+        &self.tilemap
+    }
+
+    pub fn contains(&self, grid_pos: GridPos) -> bool {
+        grid_pos.x >= 0
+            && grid_pos.y >= 0
+            && self
+                .tilemap
+                .contains(grid_pos.x as usize, grid_pos.y as usize)
+    }
+
+    pub fn tile(&self, grid_pos: GridPos) -> Option<&Tile> {
+        if !self.contains(grid_pos) {
+            return None;
+        }
+
+        self.tilemap.get(grid_pos.x as usize, grid_pos.y as usize)
+    }
+
+    /// World position of the bottom-left corner of the map.
+    fn origin(&self) -> Vec2 {
+        -Vec2::new(
+            self.tilemap.num_columns() as f32,
+            self.tilemap.num_rows() as f32,
+        ) * self.tile_size
+            / 2.0
+    }
+
+    /// World position of the center of a tile, on the given layer.
+    pub fn grid_to_world(&self, grid_pos: GridPos, layer: f32) -> Vec3 {
+        let center = self.origin()
+            + (Vec2::new(grid_pos.x as f32, grid_pos.y as f32) + 0.5) * self.tile_size;
+
+        center.extend(layer)
+    }
+
+    /// Tile under a world position, if it is on the map.
+    pub fn world_to_grid(&self, world_pos: Vec2) -> Option<GridPos> {
+        let tile = ((world_pos - self.origin()) / self.tile_size).floor();
+        let grid_pos = GridPos::new(tile.x as i32, tile.y as i32);
+
+        self.contains(grid_pos).then_some(grid_pos)
+    }
+}
+
+pub fn load_battlefield_map_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BattlefieldMap(asset_server.load(DEFAULT_MAP)));
+}
+
+pub fn create_battlefield_system(
+    battlefield_map: Res<BattlefieldMap>,
+    maps: Res<Assets<MapAsset>>,
+    tilesets: Res<Assets<Tileset>>,
+    images: Res<Assets<Image>>,
+    mut commands: Commands,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let Some(map) = maps.get(&battlefield_map.0) else {
+        return;
+    };
+    let Some(tileset) = tilesets.get(&map.tileset) else {
+        return;
+    };
+    let Some(image) = images.get(&tileset.image_handle) else {
+        return;
+    };
+    let water_image = match &tileset.water_image_handle {
+        Some(handle) => match images.get(handle) {
+            Some(water_image) => Some(water_image),
+            None => return,
+        },
+        None => None,
+    };
+
+    let battlefield = tileset
+        .validate_image(image)
+        .and_then(|_| {
+            water_image.map_or(Ok(()), |water_image| {
+                tileset.validate_water_image(water_image)
+            })
+        })
+        .and_then(|_| Battlefield::from_map(map, tileset));
+    let battlefield = match battlefield {
+        Ok(battlefield) => battlefield,
+        Err(error) => {
+            error!("Could not create the battlefield: {error}");
+            commands.remove_resource::<BattlefieldMap>();
+            return;
+        }
+    };
+
+    commands.spawn(Camera2dBundle::default());
+
+    let tiles_atlas_handle = texture_atlases.add(tileset.texture_atlas());
+    let water_animation = WaterAnimation::new(tileset, &mut texture_atlases);
+
+    for (x, y, tile) in battlefield.tilemap.iter() {
+        let grid_pos = GridPos::new(x as i32, y as i32);
+        let transform =
+            Transform::from_translation(battlefield.grid_to_world(grid_pos, TILE_LAYER));
+
+        let water = water_animation
+            .as_ref()
+            .zip(WaterTile::new(tileset, tile.tile_type));
+        match water {
+            Some((water_animation, water_tile)) => {
+                commands.spawn((
+                    SpriteSheetBundle {
+                        texture_atlas: water_animation.atlas.clone(),
+                        sprite: water_animation.sprite(&water_tile),
+                        transform,
+                        ..default()
+                    },
+                    water_tile,
+                ));
+            }
+            None => {
+                commands.spawn(SpriteSheetBundle {
+                    texture_atlas: tiles_atlas_handle.clone(),
+                    sprite: TextureAtlasSprite::new(tileset.index(tile.tile_type).unwrap()),
+                    transform,
+                    ..default()
+                });
+            }
+        }
+    }
+
+    if let Some(water_animation) = water_animation {
+        commands.insert_resource(water_animation);
+    }
+
+    info!("Loaded map \"{}\" by {}", map.name, map.author);
+
+    commands.insert_resource(battlefield);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::TileType;
+
+    fn battlefield(num_columns: usize, num_rows: usize) -> Battlefield {
+        let rows = vec![vec![TileType::Green1; num_columns]; num_rows];
+
+        Battlefield::new(16.0, Tilemap::from_rows(&rows).unwrap())
+    }
+
+    #[test]
+    fn from_map_rejects_a_map_drawn_for_another_tile_size() {
+        let tileset = Tileset::parse(
+            br#"(
+                image: "Tiles/FullTileset.png",
+                tile_size: 16.0,
+                columns: 1,
+                rows: 1,
+                tiles: { Green1: (row: 0, column: 0) },
+            )"#,
+        )
+        .unwrap();
+        let map = |tile_size| MapAsset {
+            name: "test".to_string(),
+            author: String::new(),
+            tile_size,
+            tileset_path: String::new(),
+            tileset: Handle::default(),
+            tiles: vec![vec![TileType::Green1; 2]; 2],
+            ground: None,
+            spawn_points: Vec::new(),
+            rules: Default::default(),
+        };
+
+        assert!(Battlefield::from_map(&map(16.0), &tileset).is_ok());
+        assert!(Battlefield::from_map(&map(32.0), &tileset).is_err());
+    }
+
+    #[test]
+    fn grid_to_world_centers_tiles_around_the_origin() {
+        let battlefield = battlefield(4, 2);
+
+        assert_eq!(
+            battlefield.grid_to_world(GridPos::new(0, 0), TILE_LAYER),
+            Vec3::new(-24.0, -8.0, TILE_LAYER)
+        );
+        assert_eq!(
+            battlefield.grid_to_world(GridPos::new(3, 1), UNIT_LAYER),
+            Vec3::new(24.0, 8.0, UNIT_LAYER)
+        );
+    }
+
+    #[test]
+    fn world_to_grid_finds_the_tile_under_a_point() {
+        let battlefield = battlefield(13, 6);
+
+        assert_eq!(
+            battlefield.world_to_grid(Vec2::new(-104.0, -48.0)),
+            Some(GridPos::new(0, 0))
+        );
+        assert_eq!(
+            battlefield.world_to_grid(Vec2::new(103.9, 47.9)),
+            Some(GridPos::new(12, 5))
+        );
+        assert_eq!(
+            battlefield.world_to_grid(Vec2::new(0.0, 0.0)),
+            Some(GridPos::new(6, 3))
+        );
+    }
+
+    #[test]
+    fn world_to_grid_is_none_outside_the_map() {
+        let battlefield = battlefield(13, 6);
+
+        assert_eq!(battlefield.world_to_grid(Vec2::new(-104.1, 0.0)), None);
+        assert_eq!(battlefield.world_to_grid(Vec2::new(104.0, 0.0)), None);
+        assert_eq!(battlefield.world_to_grid(Vec2::new(0.0, 48.0)), None);
+    }
+
+    #[test]
+    fn grid_and_world_conversions_round_trip() {
+        let battlefield = battlefield(40, 30);
+
+        for (x, y, _) in battlefield.tilemap().iter() {
+            let grid_pos = GridPos::new(x as i32, y as i32);
+            let world_pos = battlefield.grid_to_world(grid_pos, UNIT_LAYER);
+
+            assert_eq!(
+                battlefield.world_to_grid(world_pos.truncate()),
+                Some(grid_pos)
+            );
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Per-class data (stats, movement, weapons and sprites) loaded from
+//! `*.class.ron` files next to each class's sprite sheets.
+
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, Error, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{
+    animation::{AnimationState, UnitAnimations},
+    atlas::UnitAtlasCache,
+    terrain::MovementType,
+    unit::{Health, Level, Stats, Team, Unit, UnitClass},
+    weapon::{Weapon, WeaponType},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum HairStyle {
+    LongHair,
+    ShortHair,
+}
+
+/// One recoloured copy of a class's art.
+#[derive(Debug, Deserialize)]
+pub struct SpriteSheet {
+    pub team: Team,
+    /// The number at the end of the file name, e.g. 2 for `Archer_Blue2.png`.
+    pub palette: u8,
+    #[serde(default)]
+    pub hair: Option<HairStyle>,
+    pub path: String,
+}
+
+/// The class's art drawn with the key colours that palettes replace.
+#[derive(Debug, Deserialize)]
+pub struct ReferenceSheet {
+    #[serde(default)]
+    pub hair: Option<HairStyle>,
+    pub path: String,
+}
+
+/// How a class's sprite sheets are cut into frames.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct SheetLayout {
+    pub frame_size: f32,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "5e2d8b13-7f4a-4c69-b0e1-a38c92d64f17"]
+pub struct ClassDefinition {
+    pub class: UnitClass,
+    pub name: String,
+    pub movement_type: MovementType,
+    pub base_stats: Stats,
+    /// Percentage chance of each stat going up on level up.
+    pub growth_rates: Stats,
+    pub weapons: Vec<WeaponType>,
+    sprite_sheets: Vec<SpriteSheet>,
+    /// Missing for classes that can only use their shipped sheets.
+    #[serde(default)]
+    reference_sheets: Vec<ReferenceSheet>,
+    pub layout: SheetLayout,
+    animations: String,
+    #[serde(skip)]
+    pub animations_handle: Handle<UnitAnimations>,
+}
+
+impl ClassDefinition {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let definition: ClassDefinition = ron::de::from_bytes(bytes)?;
+        definition.validate()?;
+
+        Ok(definition)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.base_stats.hp == 0 {
+            return Err(Error::msg(format!("{} has no HP", self.name)));
+        }
+        if self.layout.frame_size <= 0.0 || self.layout.columns == 0 || self.layout.rows == 0 {
+            return Err(Error::msg(format!(
+                "{} has an empty sheet layout",
+                self.name
+            )));
+        }
+        if self.sprite_sheets.is_empty() {
+            return Err(Error::msg(format!("{} has no sprite sheets", self.name)));
+        }
+
+        Ok(())
+    }
+
+    pub fn can_wield(&self, weapon_type: WeaponType) -> bool {
+        self.weapons.contains(&weapon_type)
+    }
+
+    /// The sheet for a team's palette. Without a hair style, any of the
+    /// class's hair styles will do.
+    pub fn sprite_sheet(&self, team: Team, palette: u8, hair: Option<HairStyle>) -> Option<&str> {
+        self.sprite_sheets
+            .iter()
+            .find(|sheet| {
+                sheet.team == team
+                    && sheet.palette == palette
+                    && (hair.is_none() || sheet.hair == hair)
+            })
+            .map(|sheet| sheet.path.as_str())
+    }
+
+    pub fn reference_sheet(&self, hair: Option<HairStyle>) -> Option<&str> {
+        self.reference_sheets
+            .iter()
+            .find(|sheet| hair.is_none() || sheet.hair == hair)
+            .map(|sheet| sheet.path.as_str())
+    }
+
+    /// Average stats at a level, adding each growth rate once per level
+    /// gained.
+    pub fn stats_at_level(&self, level: u32) -> Stats {
+        self.base_stats
+            .with_growth(&self.growth_rates, level.saturating_sub(1))
+    }
+}
+
+#[derive(Default)]
+pub struct ClassDefinitionLoader;
+
+impl AssetLoader for ClassDefinitionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let mut definition = ClassDefinition::parse(bytes)?;
+
+            let animations_path = AssetPath::from(definition.animations.as_str()).to_owned();
+            UnitAnimations::parse(
+                &load_context
+                    .read_asset_bytes(animations_path.path())
+                    .await?,
+            )?
+            .check_sheet(definition.layout.columns, definition.layout.rows)
+            .map_err(|error| Error::msg(format!("{}: {error}", definition.name)))?;
+            definition.animations_handle = load_context.get_handle(animations_path.clone());
+
+            load_context
+                .set_default_asset(LoadedAsset::new(definition).with_dependency(animations_path));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["class.ron"]
+    }
+}
+
+/// Handles to the definition of every class, kept alive for the whole game.
+#[derive(Resource)]
+pub struct ClassDefinitions(HashMap<UnitClass, Handle<ClassDefinition>>);
+
+impl ClassDefinitions {
+    pub fn get<'a>(
+        &self,
+        class: UnitClass,
+        definitions: &'a Assets<ClassDefinition>,
+    ) -> Option<&'a ClassDefinition> {
+        definitions.get(self.0.get(&class)?)
+    }
+
+    pub fn all_loaded(&self, definitions: &Assets<ClassDefinition>) -> bool {
+        self.0.values().all(|handle| definitions.contains(handle))
+    }
+}
+
+pub fn load_class_definitions_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handles = UnitClass::ALL
+        .into_iter()
+        .map(|class| {
+            let path = format!("Sprite Sheets/{0}/{0}.class.ron", class.name());
+            (class, asset_server.load(path))
+        })
+        .collect();
+
+    commands.insert_resource(ClassDefinitions(handles));
+}
+
+pub fn class_definitions_loaded(
+    class_definitions: Option<Res<ClassDefinitions>>,
+    definitions: Res<Assets<ClassDefinition>>,
+) -> bool {
+    class_definitions.is_some_and(|class_definitions| class_definitions.all_loaded(&definitions))
+}
+
+type ReloadedUnit<'a> = (
+    Entity,
+    &'a UnitClass,
+    &'a Level,
+    &'a mut Stats,
+    &'a mut Health,
+    &'a mut AnimationState,
+    &'a mut Handle<TextureAtlas>,
+    Option<&'a Weapon>,
+);
+
+/// Applies edited class files to the units already on the field: stats,
+/// animations and sheet layout are swapped in, and weapons the class can no
+/// longer wield are dropped. Units keep the fraction of HP they had left.
+/// The movement type and weapon types are read from the definition when
+/// they are needed, so they apply right away. Which sprite sheet a unit is
+/// drawn from is picked when it is deployed and only changes on restart.
+/// A sheet layout the class's clips don't fit on is left unapplied.
+#[allow(clippy::too_many_arguments)]
+pub fn reload_class_definitions_system(
+    mut events: EventReader<AssetEvent<ClassDefinition>>,
+    definitions: Res<Assets<ClassDefinition>>,
+    unit_animations: Res<Assets<UnitAnimations>>,
+    mut atlas_cache: ResMut<UnitAtlasCache>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut units: Query<ReloadedUnit, With<Unit>>,
+    mut commands: Commands,
+) {
+    for event in events.iter() {
+        let AssetEvent::Modified { handle } = event else {
+            continue;
+        };
+        let Some(definition) = definitions.get(handle) else {
+            continue;
+        };
+
+        info!("reloaded the {} class", definition.name);
+        let sheet_fits = match unit_animations.get(&definition.animations_handle) {
+            Some(animations) => animations
+                .check_sheet(definition.layout.columns, definition.layout.rows)
+                .map_err(|error| error!("{}: {error}", definition.name))
+                .is_ok(),
+            // The loader checked the clips against the layout already.
+            None => true,
+        };
+        for (entity, class, level, mut stats, mut health, mut animation, mut atlas, weapon) in
+            &mut units
+        {
+            if *class != definition.class {
+                continue;
+            }
+
+            let new_stats = definition.stats_at_level(level.0);
+            if !health.is_dead() {
+                health.current = (health.current * new_stats.hp / stats.hp.max(1)).max(1);
+            }
+            *stats = new_stats;
+
+            if sheet_fits {
+                animation.set_sheet(
+                    definition.animations_handle.clone(),
+                    definition.layout.columns,
+                );
+                if let Some(image) = texture_atlases
+                    .get(&atlas)
+                    .map(|texture_atlas| texture_atlas.texture.clone())
+                {
+                    *atlas =
+                        atlas_cache.get_or_load(&image, definition.layout, &mut texture_atlases);
+                }
+            }
+
+            if let Some(weapon) = weapon.filter(|weapon| !definition.can_wield(weapon.weapon_type))
+            {
+                warn!(
+                    "{} can no longer wield {}, dropping it",
+                    definition.name, weapon.name
+                );
+                commands.entity(entity).remove::<Weapon>();
+            }
+        }
+    }
+}
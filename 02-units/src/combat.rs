@@ -0,0 +1,706 @@
+//! Combat between two units: the odds of each blow, worked out from stats,
+//! weapons and terrain, and the strikes that follow from rolling them.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    animation::{AnimationState, ATTACK_CLIP, DEATH_CLIP, HURT_CLIP},
+    battlefield::Battlefield,
+    class::{ClassDefinition, ClassDefinitions},
+    facing::FaceTile,
+    grid::GridPos,
+    terrain::{MovementType, Terrain, TerrainClass},
+    tilemap::Tile,
+    turn::TurnStatus,
+    unit::{Health, Stats, Team, UnitClass},
+    walk::UnitMoved,
+    weapon::{ActiveArmory, Armory, Weapon, WeaponTriangle},
+};
+
+/// How much faster than its foe a unit must be to strike twice.
+pub const DOUBLE_ATTACK_SPEED: u32 = 4;
+/// Critical hits deal this many times the normal damage.
+pub const CRIT_MULTIPLIER: u32 = 3;
+/// Weapons effective against a unit's movement type have this many times
+/// their might.
+pub const EFFECTIVE_MIGHT_MULTIPLIER: u32 = 3;
+
+/// One side of a fight.
+#[derive(Clone, Copy, Debug)]
+pub struct Combatant<'a> {
+    pub stats: Stats,
+    pub movement_type: MovementType,
+    pub weapon: Option<&'a Weapon>,
+    /// The ground the unit stands on.
+    pub terrain: &'static Terrain,
+}
+
+impl Combatant<'_> {
+    /// Speed, less one point for each point of weapon weight the unit is
+    /// too weak to carry.
+    pub fn attack_speed(&self) -> u32 {
+        let weight = self.weapon.map_or(0, |weapon| weapon.weight);
+        self.stats
+            .speed
+            .saturating_sub(weight.saturating_sub(self.stats.strength))
+    }
+}
+
+/// The odds of one side's blows landing, and what they do when they do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttackForecast {
+    pub damage: u32,
+    /// Chance to hit, in percent.
+    pub hit: u32,
+    /// Chance that a hit is critical, in percent.
+    pub crit: u32,
+    /// 2 if the side is fast enough to strike twice.
+    pub strikes: u32,
+}
+
+impl AttackForecast {
+    fn new(
+        attacker: &Combatant,
+        weapon: &Weapon,
+        defender: &Combatant,
+        triangle: &WeaponTriangle,
+    ) -> Self {
+        let (power, protection) = if weapon.weapon_type.is_magic() {
+            (attacker.stats.magic, defender.stats.resistance)
+        } else {
+            (attacker.stats.strength, defender.stats.defense)
+        };
+        let might = if weapon.is_effective_against(defender.movement_type) {
+            weapon.might * EFFECTIVE_MIGHT_MULTIPLIER
+        } else {
+            weapon.might
+        };
+        let advantage = defender.weapon.map_or(0, |defending| {
+            triangle.advantage(weapon.weapon_type, defending.weapon_type)
+        });
+
+        let attack = (power + might) as i32 + advantage * triangle.damage;
+        let damage = attack - (protection as i32 + defender.terrain.defense);
+
+        let accuracy = (weapon.hit + attacker.stats.skill * 2 + attacker.stats.luck / 2) as i32
+            + advantage * triangle.hit;
+        let avoid =
+            (defender.attack_speed() * 2 + defender.stats.luck) as i32 + defender.terrain.avoid;
+        let crit = (weapon.crit + attacker.stats.skill / 2) as i32 - defender.stats.luck as i32;
+
+        AttackForecast {
+            damage: damage.max(0) as u32,
+            hit: (accuracy - avoid).clamp(0, 100) as u32,
+            crit: crit.clamp(0, 100) as u32,
+            strikes: if attacker.attack_speed() >= defender.attack_speed() + DOUBLE_ATTACK_SPEED {
+                2
+            } else {
+                1
+            },
+        }
+    }
+}
+
+/// What both sides can expect from a fight. The defender only strikes back
+/// if its weapon reaches the attacker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CombatForecast {
+    pub attacker: AttackForecast,
+    pub defender: Option<AttackForecast>,
+}
+
+/// Forecasts a fight between units `distance` tiles apart. `None` if the
+/// attacker has no weapon that reaches.
+pub fn forecast(
+    attacker: &Combatant,
+    defender: &Combatant,
+    distance: u32,
+    triangle: &WeaponTriangle,
+) -> Option<CombatForecast> {
+    let weapon = attacker.weapon.filter(|weapon| weapon.in_range(distance))?;
+    let counter = defender.weapon.filter(|weapon| weapon.in_range(distance));
+
+    Some(CombatForecast {
+        attacker: AttackForecast::new(attacker, weapon, defender, triangle),
+        defender: counter.map(|weapon| AttackForecast::new(defender, weapon, attacker, triangle)),
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Attacker,
+    Defender,
+}
+
+/// A single blow, by `side` against the other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Strike {
+    pub side: Side,
+    pub hit: bool,
+    pub critical: bool,
+    /// Damage dealt, never more than the target had left.
+    pub damage: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CombatOutcome {
+    /// The blows in the order they were struck.
+    pub strikes: Vec<Strike>,
+    pub attacker_hp: u32,
+    pub defender_hp: u32,
+}
+
+/// Rolls a forecast fight. The attacker strikes first, then the defender
+/// counters, then whoever is fast enough strikes again. The fight ends as
+/// soon as either side falls.
+pub fn resolve(
+    forecast: &CombatForecast,
+    attacker_hp: u32,
+    defender_hp: u32,
+    rng: &mut impl Rng,
+) -> CombatOutcome {
+    let mut outcome = CombatOutcome {
+        strikes: Vec::new(),
+        attacker_hp,
+        defender_hp,
+    };
+
+    let mut order = vec![(Side::Attacker, forecast.attacker)];
+    order.extend(forecast.defender.map(|counter| (Side::Defender, counter)));
+    let follow_ups: Vec<_> = order
+        .iter()
+        .filter(|(_, attack)| attack.strikes > 1)
+        .copied()
+        .collect();
+    order.extend(follow_ups);
+
+    for (side, attack) in order {
+        if outcome.attacker_hp == 0 || outcome.defender_hp == 0 {
+            break;
+        }
+
+        let hit = rng.gen_range(0..100) < attack.hit;
+        let critical = hit && rng.gen_range(0..100) < attack.crit;
+        let target_hp = match side {
+            Side::Attacker => &mut outcome.defender_hp,
+            Side::Defender => &mut outcome.attacker_hp,
+        };
+        let damage = match (hit, critical) {
+            (false, _) => 0,
+            (true, false) => attack.damage,
+            (true, true) => attack.damage * CRIT_MULTIPLIER,
+        }
+        .min(*target_hp);
+        *target_hp -= damage;
+
+        outcome.strikes.push(Strike {
+            side,
+            hit,
+            critical,
+            damage,
+        });
+    }
+
+    outcome
+}
+
+/// The random numbers behind every fight. Seeded so a battle can be played
+/// back exactly.
+#[derive(Resource)]
+pub struct CombatRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl CombatRng {
+    pub fn from_seed(seed: u64) -> Self {
+        CombatRng {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for CombatRng {
+    fn default() -> Self {
+        let seed = rand::random();
+        info!("Combat seed: {seed}");
+
+        CombatRng::from_seed(seed)
+    }
+}
+
+/// Sent after a fight has been rolled and its damage applied.
+pub struct CombatResolved {
+    pub attacker: Entity,
+    pub defender: Entity,
+    pub attacker_pos: GridPos,
+    pub defender_pos: GridPos,
+    pub outcome: CombatOutcome,
+}
+
+/// What fights are worked out from, besides the units themselves.
+#[derive(SystemParam)]
+pub struct CombatContext<'w> {
+    battlefield: Res<'w, Battlefield>,
+    class_definitions: Res<'w, ClassDefinitions>,
+    definitions: Res<'w, Assets<ClassDefinition>>,
+    active_armory: Res<'w, ActiveArmory>,
+    armories: Res<'w, Assets<Armory>>,
+}
+
+impl CombatContext<'_> {
+    pub fn triangle(&self) -> Option<&WeaponTriangle> {
+        self.armories
+            .get(&self.active_armory.0)
+            .map(|armory| &armory.triangle)
+    }
+
+    /// A unit as it would fight from `grid_pos`.
+    pub fn combatant<'a>(
+        &self,
+        grid_pos: GridPos,
+        class: UnitClass,
+        stats: Stats,
+        weapon: Option<&'a Weapon>,
+    ) -> Option<Combatant<'a>> {
+        let definition = self.class_definitions.get(class, &self.definitions)?;
+        let terrain = self
+            .battlefield
+            .tile(grid_pos)
+            .map_or(TerrainClass::Grass.terrain(), Tile::terrain);
+
+        Some(Combatant {
+            stats,
+            movement_type: definition.movement_type,
+            weapon,
+            terrain,
+        })
+    }
+}
+
+type Fighter<'a> = (
+    &'a GridPos,
+    &'a Team,
+    &'a UnitClass,
+    &'a Stats,
+    &'a mut Health,
+    Option<&'a mut Weapon>,
+    &'a mut TurnStatus,
+    &'a mut AnimationState,
+);
+
+/// Asks for `attacker` to fight `defender` from where it stands.
+pub struct AttackOrder {
+    pub attacker: Entity,
+    pub defender: Entity,
+}
+
+/// An attack to make once the unit has finished walking into reach.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PendingAttack {
+    pub target: Entity,
+}
+
+pub fn attack_after_walk_system(
+    mut moved: EventReader<UnitMoved>,
+    pending: Query<&PendingAttack>,
+    mut orders: EventWriter<AttackOrder>,
+    mut commands: Commands,
+) {
+    for event in moved.iter() {
+        if let Ok(pending) = pending.get(event.entity) {
+            orders.send(AttackOrder {
+                attacker: event.entity,
+                defender: pending.target,
+            });
+            commands.entity(event.entity).remove::<PendingAttack>();
+        }
+    }
+}
+
+/// Carries out attack orders whose weapon reaches the target. Weapons wear
+/// down with every strike and are lost when they break.
+pub fn attack_system(
+    mut orders: EventReader<AttackOrder>,
+    context: CombatContext,
+    mut rng: ResMut<CombatRng>,
+    mut units: Query<Fighter>,
+    mut face_tile: EventWriter<FaceTile>,
+    mut resolved: EventWriter<CombatResolved>,
+    mut commands: Commands,
+) {
+    let Some(triangle) = context.triangle() else {
+        return;
+    };
+
+    for order in orders.iter() {
+        let Ok([attacker, defender]) = units.get_many_mut([order.attacker, order.defender]) else {
+            continue;
+        };
+        let (
+            attacker_pos,
+            attacker_team,
+            attacker_class,
+            attacker_stats,
+            mut attacker_health,
+            mut attacker_weapon,
+            mut status,
+            mut attacker_animation,
+        ) = attacker;
+        let (
+            defender_pos,
+            defender_team,
+            defender_class,
+            defender_stats,
+            mut defender_health,
+            mut defender_weapon,
+            _,
+            mut defender_animation,
+        ) = defender;
+        if attacker_team == defender_team || attacker_health.is_dead() || defender_health.is_dead()
+        {
+            continue;
+        }
+
+        let (Some(attacking), Some(defending)) = (
+            context.combatant(
+                *attacker_pos,
+                *attacker_class,
+                *attacker_stats,
+                attacker_weapon.as_deref(),
+            ),
+            context.combatant(
+                *defender_pos,
+                *defender_class,
+                *defender_stats,
+                defender_weapon.as_deref(),
+            ),
+        ) else {
+            continue;
+        };
+        let distance = attacker_pos.distance(*defender_pos);
+        let Some(forecast) = forecast(&attacking, &defending, distance, triangle) else {
+            continue;
+        };
+
+        let outcome = resolve(
+            &forecast,
+            attacker_health.current,
+            defender_health.current,
+            &mut rng.rng,
+        );
+        attacker_health.current = outcome.attacker_hp;
+        defender_health.current = outcome.defender_hp;
+        status.has_moved = true;
+        status.has_acted = true;
+
+        for (entity, side, weapon) in [
+            (order.attacker, Side::Attacker, &mut attacker_weapon),
+            (order.defender, Side::Defender, &mut defender_weapon),
+        ] {
+            let Some(weapon) = weapon else {
+                continue;
+            };
+            for _ in outcome.strikes.iter().filter(|strike| strike.side == side) {
+                if weapon.wear() {
+                    info!("{} broke", weapon.name);
+                    commands.entity(entity).remove::<Weapon>();
+                    break;
+                }
+            }
+        }
+
+        for (animation, side, health) in [
+            (&mut attacker_animation, Side::Attacker, &attacker_health),
+            (&mut defender_animation, Side::Defender, &defender_health),
+        ] {
+            let struck = outcome.strikes.iter().any(|strike| strike.side == side);
+            if struck {
+                animation.play(ATTACK_CLIP);
+                if health.is_dead() {
+                    animation.queue(DEATH_CLIP);
+                }
+            } else if health.is_dead() {
+                animation.play(DEATH_CLIP);
+            } else if outcome
+                .strikes
+                .iter()
+                .any(|strike| strike.side != side && strike.hit)
+            {
+                animation.play(HURT_CLIP);
+            }
+        }
+
+        face_tile.send(FaceTile {
+            entity: order.attacker,
+            target: *defender_pos,
+        });
+        face_tile.send(FaceTile {
+            entity: order.defender,
+            target: *attacker_pos,
+        });
+        resolved.send(CombatResolved {
+            attacker: order.attacker,
+            defender: order.defender,
+            attacker_pos: *attacker_pos,
+            defender_pos: *defender_pos,
+            outcome,
+        });
+    }
+}
+
+/// Takes fallen units off the battlefield once their death clip has played
+/// out.
+pub fn remove_fallen_units_system(
+    units: Query<(Entity, &Health, &AnimationState)>,
+    mut commands: Commands,
+) {
+    for (entity, health, animation) in &units {
+        if health.is_dead() && animation.clip() == DEATH_CLIP && animation.is_finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weapon::WeaponType;
+
+    fn stats(strength: u32, defense: u32, speed: u32) -> Stats {
+        Stats {
+            hp: 20,
+            strength,
+            magic: 4,
+            defense,
+            resistance: 2,
+            speed,
+            skill: 5,
+            luck: 2,
+            movement: 5,
+        }
+    }
+
+    fn weapon(weapon_type: WeaponType, min_range: u32, max_range: u32) -> Weapon {
+        Weapon {
+            name: format!("{weapon_type:?}"),
+            weapon_type,
+            might: 5,
+            hit: 90,
+            crit: 0,
+            weight: 0,
+            min_range,
+            max_range,
+            durability: 40,
+            effective_against: Vec::new(),
+        }
+    }
+
+    fn combatant(stats: Stats, weapon: &Weapon, terrain: TerrainClass) -> Combatant<'_> {
+        Combatant {
+            stats,
+            movement_type: MovementType::Foot,
+            weapon: Some(weapon),
+            terrain: terrain.terrain(),
+        }
+    }
+
+    fn triangle() -> WeaponTriangle {
+        WeaponTriangle {
+            advantages: vec![
+                (WeaponType::Sword, WeaponType::Axe),
+                (WeaponType::Axe, WeaponType::Lance),
+                (WeaponType::Lance, WeaponType::Sword),
+            ],
+            hit: 15,
+            damage: 1,
+        }
+    }
+
+    #[test]
+    fn forecast_uses_stats_weapon_and_terrain() {
+        let sword = weapon(WeaponType::Sword, 1, 1);
+        let attacker = combatant(stats(6, 3, 7), &sword, TerrainClass::Grass);
+        let in_the_grass = combatant(stats(6, 3, 7), &sword, TerrainClass::Grass);
+        let in_the_open = combatant(stats(6, 3, 7), &sword, TerrainClass::Dirt);
+
+        let sheltered = forecast(&attacker, &in_the_grass, 1, &triangle())
+            .unwrap()
+            .attacker;
+        let exposed = forecast(&attacker, &in_the_open, 1, &triangle())
+            .unwrap()
+            .attacker;
+
+        assert_eq!(
+            exposed.damage,
+            6 + 5 - 3 - in_the_open.terrain.defense as u32
+        );
+        assert_eq!(sheltered.damage, exposed.damage);
+        assert_eq!(
+            sheltered.hit + in_the_grass.terrain.avoid as u32,
+            exposed.hit
+        );
+        assert_eq!(exposed.strikes, 1);
+    }
+
+    #[test]
+    fn magic_targets_resistance() {
+        let fire = weapon(WeaponType::Tome, 1, 2);
+        let wizard = combatant(stats(0, 1, 5), &fire, TerrainClass::Dirt);
+        let knight = combatant(stats(6, 12, 5), &fire, TerrainClass::Dirt);
+
+        let attack = forecast(&wizard, &knight, 1, &triangle()).unwrap().attacker;
+
+        assert_eq!(attack.damage, 4 + 5 - 2);
+    }
+
+    #[test]
+    fn faster_units_strike_twice() {
+        let sword = weapon(WeaponType::Sword, 1, 1);
+        let fast = combatant(stats(5, 3, 9), &sword, TerrainClass::Dirt);
+        let slow = combatant(stats(5, 3, 5), &sword, TerrainClass::Dirt);
+
+        let forecast = forecast(&fast, &slow, 1, &triangle()).unwrap();
+
+        assert_eq!(forecast.attacker.strikes, 2);
+        assert_eq!(forecast.defender.unwrap().strikes, 1);
+    }
+
+    #[test]
+    fn heavy_weapons_slow_weak_units() {
+        let light = weapon(WeaponType::Sword, 1, 1);
+        let heavy = Weapon {
+            weight: 9,
+            ..weapon(WeaponType::Axe, 1, 1)
+        };
+        let nimble = combatant(stats(5, 3, 9), &light, TerrainClass::Dirt);
+        let weighed_down = combatant(stats(5, 3, 9), &heavy, TerrainClass::Dirt);
+
+        assert_eq!(weighed_down.attack_speed(), 5);
+        assert_eq!(
+            forecast(&nimble, &weighed_down, 1, &WeaponTriangle::default())
+                .unwrap()
+                .attacker
+                .strikes,
+            2
+        );
+    }
+
+    #[test]
+    fn triangle_shifts_hit_and_damage() {
+        let sword = weapon(WeaponType::Sword, 1, 1);
+        let axe = weapon(WeaponType::Axe, 1, 1);
+        let swordsman = combatant(stats(5, 3, 5), &sword, TerrainClass::Dirt);
+        let axeman = combatant(stats(5, 3, 5), &axe, TerrainClass::Dirt);
+
+        let neutral = forecast(&swordsman, &axeman, 1, &WeaponTriangle::default()).unwrap();
+        let weighted = forecast(&swordsman, &axeman, 1, &triangle()).unwrap();
+
+        assert_eq!(weighted.attacker.damage, neutral.attacker.damage + 1);
+        assert_eq!(weighted.attacker.hit, (neutral.attacker.hit + 15).min(100));
+        let (countered, neutral_counter) = (weighted.defender.unwrap(), neutral.defender.unwrap());
+        assert_eq!(countered.damage + 1, neutral_counter.damage);
+        assert_eq!(countered.hit + 15, neutral_counter.hit);
+    }
+
+    #[test]
+    fn effective_weapons_triple_might() {
+        let hammer = Weapon {
+            effective_against: vec![MovementType::Armored],
+            ..weapon(WeaponType::Axe, 1, 1)
+        };
+        let fighter = combatant(stats(5, 3, 5), &hammer, TerrainClass::Dirt);
+        let mut knight = combatant(stats(5, 3, 5), &hammer, TerrainClass::Dirt);
+        knight.movement_type = MovementType::Armored;
+
+        let attack = forecast(&fighter, &knight, 1, &triangle())
+            .unwrap()
+            .attacker;
+
+        assert_eq!(attack.damage, 5 + 5 * EFFECTIVE_MIGHT_MULTIPLIER - 3);
+    }
+
+    #[test]
+    fn no_counter_out_of_range() {
+        let fire = weapon(WeaponType::Tome, 1, 2);
+        let sword = weapon(WeaponType::Sword, 1, 1);
+        let wizard = combatant(stats(0, 1, 5), &fire, TerrainClass::Dirt);
+        let fighter = combatant(stats(5, 3, 5), &sword, TerrainClass::Dirt);
+
+        assert!(forecast(&wizard, &fighter, 1, &triangle())
+            .unwrap()
+            .defender
+            .is_some());
+        assert!(forecast(&wizard, &fighter, 2, &triangle())
+            .unwrap()
+            .defender
+            .is_none());
+        assert!(forecast(&fighter, &wizard, 2, &triangle()).is_none());
+    }
+
+    fn sure_hits(attacker_strikes: u32, defender_strikes: u32) -> CombatForecast {
+        let attack = |strikes| AttackForecast {
+            damage: 6,
+            hit: 100,
+            crit: 0,
+            strikes,
+        };
+
+        CombatForecast {
+            attacker: attack(attacker_strikes),
+            defender: Some(attack(defender_strikes)),
+        }
+    }
+
+    #[test]
+    fn strikes_alternate_with_follow_ups_last() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let outcome = resolve(&sure_hits(2, 1), 20, 20, &mut rng);
+
+        let sides: Vec<_> = outcome.strikes.iter().map(|strike| strike.side).collect();
+        assert_eq!(sides, [Side::Attacker, Side::Defender, Side::Attacker]);
+        assert_eq!(outcome.attacker_hp, 14);
+        assert_eq!(outcome.defender_hp, 8);
+    }
+
+    #[test]
+    fn fight_ends_when_a_side_falls() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let outcome = resolve(&sure_hits(2, 1), 20, 5, &mut rng);
+
+        assert_eq!(outcome.strikes.len(), 1);
+        assert_eq!(outcome.strikes[0].damage, 5);
+        assert_eq!(outcome.defender_hp, 0);
+    }
+
+    #[test]
+    fn same_seed_same_outcome() {
+        let forecast = CombatForecast {
+            attacker: AttackForecast {
+                damage: 3,
+                hit: 60,
+                crit: 20,
+                strikes: 2,
+            },
+            defender: Some(AttackForecast {
+                damage: 2,
+                hit: 50,
+                crit: 10,
+                strikes: 1,
+            }),
+        };
+
+        let outcomes: Vec<_> = (0..2)
+            .map(|_| resolve(&forecast, 30, 30, &mut CombatRng::from_seed(42).rng))
+            .collect();
+
+        assert_eq!(outcomes[0], outcomes[1]);
+    }
+}
@@ -0,0 +1,230 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    battlefield::Battlefield,
+    grid::GridPos,
+    picking::{SelectionCancelled, TileHovered, TileSelected},
+    unit::{unit_at, Health, Unit},
+};
+
+const CURSOR_SHEET: &str = "UI Elements/SelectionCursor.png";
+const CURSOR_SPRITE_SIZE: f32 = 16.0;
+const CURSOR_SHEET_COLUMNS: usize = 4;
+const CURSOR_SHEET_ROWS: usize = 2;
+
+/// Drawn above the units so the cursor stays visible on occupied tiles.
+pub const CURSOR_LAYER: f32 = 2.0;
+
+/// How long a direction has to be held before the cursor starts repeating,
+/// and the time between repeated steps after that.
+const REPEAT_DELAY: f32 = 0.3;
+const REPEAT_INTERVAL: f32 = 0.08;
+
+#[derive(Component)]
+pub struct GridCursor {
+    direction: IVec2,
+    repeat: Timer,
+}
+
+impl Default for GridCursor {
+    fn default() -> Self {
+        Self {
+            direction: IVec2::ZERO,
+            repeat: Timer::from_seconds(REPEAT_DELAY, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Keyboard and gamepad bindings for the grid cursor.
+#[derive(SystemParam)]
+pub struct CursorInput<'w> {
+    keyboard: Res<'w, Input<KeyCode>>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+}
+
+impl CursorInput<'_> {
+    fn gamepad_pressed(&self, button_type: GamepadButtonType) -> bool {
+        self.gamepads.iter().any(|gamepad| {
+            self.gamepad_buttons
+                .pressed(GamepadButton::new(gamepad, button_type))
+        })
+    }
+
+    fn gamepad_just_pressed(&self, button_type: GamepadButtonType) -> bool {
+        self.gamepads.iter().any(|gamepad| {
+            self.gamepad_buttons
+                .just_pressed(GamepadButton::new(gamepad, button_type))
+        })
+    }
+
+    fn held(&self, keys: [KeyCode; 2], button_type: GamepadButtonType) -> bool {
+        self.keyboard.any_pressed(keys) || self.gamepad_pressed(button_type)
+    }
+
+    /// The direction currently held, combining opposite directions so they
+    /// cancel out.
+    pub fn direction(&self) -> IVec2 {
+        let mut direction = IVec2::ZERO;
+        if self.held([KeyCode::Up, KeyCode::W], GamepadButtonType::DPadUp) {
+            direction.y += 1;
+        }
+        if self.held([KeyCode::Down, KeyCode::S], GamepadButtonType::DPadDown) {
+            direction.y -= 1;
+        }
+        if self.held([KeyCode::Right, KeyCode::D], GamepadButtonType::DPadRight) {
+            direction.x += 1;
+        }
+        if self.held([KeyCode::Left, KeyCode::A], GamepadButtonType::DPadLeft) {
+            direction.x -= 1;
+        }
+        direction
+    }
+
+    pub fn just_confirmed(&self) -> bool {
+        self.keyboard
+            .any_just_pressed([KeyCode::Return, KeyCode::Space])
+            || self.gamepad_just_pressed(GamepadButtonType::South)
+    }
+
+    pub fn just_cancelled(&self) -> bool {
+        self.keyboard
+            .any_just_pressed([KeyCode::Escape, KeyCode::Back])
+            || self.gamepad_just_pressed(GamepadButtonType::East)
+    }
+
+    pub fn just_toggled_danger_zone(&self) -> bool {
+        self.keyboard.just_pressed(KeyCode::Tab)
+            || self.gamepad_just_pressed(GamepadButtonType::North)
+    }
+}
+
+/// Spawns the cursor on the middle of the map.
+pub fn spawn_grid_cursor_system(
+    battlefield: Res<Battlefield>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut commands: Commands,
+) {
+    let texture_atlas = TextureAtlas::from_grid(
+        asset_server.load(CURSOR_SHEET),
+        Vec2::new(CURSOR_SPRITE_SIZE, CURSOR_SPRITE_SIZE),
+        CURSOR_SHEET_COLUMNS,
+        CURSOR_SHEET_ROWS,
+        None,
+        None,
+    );
+
+    let tilemap = battlefield.tilemap();
+    let grid_pos = GridPos::new(
+        tilemap.num_columns() as i32 / 2,
+        tilemap.num_rows() as i32 / 2,
+    );
+
+    commands.spawn((
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(texture_atlas),
+            sprite: TextureAtlasSprite::new(0),
+            transform: Transform::from_translation(
+                battlefield.grid_to_world(grid_pos, CURSOR_LAYER),
+            ),
+            ..default()
+        },
+        grid_pos,
+        GridCursor::default(),
+    ));
+}
+
+/// Steps the cursor one tile when a direction is pressed and keeps stepping
+/// while it is held, staying inside the map.
+pub fn move_grid_cursor_system(
+    time: Res<Time>,
+    input: CursorInput,
+    battlefield: Res<Battlefield>,
+    units: Query<(Entity, &GridPos, &Health), With<Unit>>,
+    mut cursors: Query<(&mut GridCursor, &mut GridPos), Without<Unit>>,
+    mut hovered_events: EventWriter<TileHovered>,
+) {
+    let direction = input.direction();
+    let tilemap = battlefield.tilemap();
+
+    for (mut cursor, mut grid_pos) in &mut cursors {
+        let step = if direction == IVec2::ZERO {
+            false
+        } else if direction != cursor.direction {
+            cursor
+                .repeat
+                .set_duration(Duration::from_secs_f32(REPEAT_DELAY));
+            cursor.repeat.reset();
+            true
+        } else if cursor.repeat.tick(time.delta()).just_finished() {
+            cursor
+                .repeat
+                .set_duration(Duration::from_secs_f32(REPEAT_INTERVAL));
+            true
+        } else {
+            false
+        };
+        cursor.direction = direction;
+
+        if !step {
+            continue;
+        }
+
+        let target = GridPos::new(
+            (grid_pos.x + direction.x).clamp(0, tilemap.num_columns() as i32 - 1),
+            (grid_pos.y + direction.y).clamp(0, tilemap.num_rows() as i32 - 1),
+        );
+        if target != *grid_pos {
+            *grid_pos = target;
+            hovered_events.send(TileHovered {
+                grid_pos: target,
+                unit: unit_at(&units, target),
+            });
+        }
+    }
+}
+
+/// Sends the same selection events as mouse picking for the tile under the
+/// cursor.
+pub fn grid_cursor_action_system(
+    input: CursorInput,
+    units: Query<(Entity, &GridPos, &Health), With<Unit>>,
+    cursors: Query<&GridPos, With<GridCursor>>,
+    mut selected_events: EventWriter<TileSelected>,
+    mut cancelled_events: EventWriter<SelectionCancelled>,
+) {
+    let Ok(grid_pos) = cursors.get_single() else {
+        return;
+    };
+
+    if input.just_confirmed() {
+        selected_events.send(TileSelected {
+            grid_pos: *grid_pos,
+            unit: unit_at(&units, *grid_pos),
+        });
+    }
+
+    if input.just_cancelled() {
+        cancelled_events.send(SelectionCancelled);
+    }
+}
+
+/// Keeps the cursor on the tile under the mouse, so both input paths share
+/// one highlighted tile.
+pub fn follow_hovered_tile_system(
+    mut hovered_events: EventReader<TileHovered>,
+    mut cursors: Query<&mut GridPos, (With<GridCursor>, Without<Unit>)>,
+) {
+    let Some(event) = hovered_events.iter().last() else {
+        return;
+    };
+
+    for mut grid_pos in &mut cursors {
+        if *grid_pos != event.grid_pos {
+            *grid_pos = event.grid_pos;
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! One-shot visual effects played over a tile, like the flash of a critical
+//! hit, along with the screen shake and hit-stop that go with them.
+
+use std::collections::HashMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    battlefield::Battlefield,
+    combat::{CombatResolved, Side},
+    grid::GridPos,
+};
+
+/// Drawn above the units and below the cursor.
+pub const EFFECT_LAYER: f32 = 1.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EffectId {
+    CriticalHit,
+}
+
+/// How an effect looks and how hard it hits.
+struct EffectSheet {
+    path: &'static str,
+    frame_size: f32,
+    frames: usize,
+    frame_seconds: f32,
+    /// How far the screen shakes, in world units. 0 for no shake.
+    shake: f32,
+    shake_seconds: f32,
+    /// How long the rest of the game freezes when the effect starts.
+    hit_stop_seconds: f32,
+}
+
+impl EffectId {
+    fn sheet(self) -> EffectSheet {
+        match self {
+            EffectId::CriticalHit => EffectSheet {
+                path: "Effects/CriticalHit.png",
+                frame_size: 32.0,
+                frames: 4,
+                frame_seconds: 0.06,
+                shake: 2.0,
+                shake_seconds: 0.25,
+                hit_stop_seconds: 0.12,
+            },
+        }
+    }
+}
+
+/// A playing effect. It despawns itself after its last frame.
+#[derive(Component)]
+pub struct Effect {
+    frame: usize,
+    frames: usize,
+    timer: Timer,
+}
+
+/// Texture atlases of the effects played so far.
+#[derive(Resource, Default)]
+pub struct EffectAtlases(HashMap<EffectId, Handle<TextureAtlas>>);
+
+#[derive(Resource, Default)]
+pub struct ScreenShake {
+    strength: f32,
+    timer: Timer,
+}
+
+/// Freezes game time for a moment to sell the weight of a blow. Effects and
+/// the screen shake keep playing through it.
+#[derive(Resource, Default)]
+pub struct HitStop {
+    timer: Timer,
+}
+
+/// Everything needed to start an effect.
+#[derive(SystemParam)]
+pub struct EffectSpawner<'w, 's> {
+    commands: Commands<'w, 's>,
+    asset_server: Res<'w, AssetServer>,
+    texture_atlases: ResMut<'w, Assets<TextureAtlas>>,
+    atlases: ResMut<'w, EffectAtlases>,
+    battlefield: Res<'w, Battlefield>,
+    shake: ResMut<'w, ScreenShake>,
+    hit_stop: ResMut<'w, HitStop>,
+}
+
+impl EffectSpawner<'_, '_> {
+    /// Plays an effect once over a tile.
+    pub fn spawn_effect(&mut self, effect: EffectId, grid_pos: GridPos) -> Entity {
+        let sheet = effect.sheet();
+        let texture_atlas = self
+            .atlases
+            .0
+            .entry(effect)
+            .or_insert_with(|| {
+                self.texture_atlases.add(TextureAtlas::from_grid(
+                    self.asset_server.load(sheet.path),
+                    Vec2::splat(sheet.frame_size),
+                    sheet.frames,
+                    1,
+                    None,
+                    None,
+                ))
+            })
+            .clone();
+
+        if sheet.shake > 0.0 {
+            self.shake.strength = self.shake.strength.max(sheet.shake);
+            self.shake.timer = Timer::from_seconds(sheet.shake_seconds, TimerMode::Once);
+        }
+        if sheet.hit_stop_seconds > self.hit_stop.timer.remaining_secs() {
+            self.hit_stop.timer = Timer::from_seconds(sheet.hit_stop_seconds, TimerMode::Once);
+        }
+
+        self.commands
+            .spawn((
+                SpriteSheetBundle {
+                    texture_atlas,
+                    sprite: TextureAtlasSprite::new(0),
+                    transform: Transform::from_translation(
+                        self.battlefield.grid_to_world(grid_pos, EFFECT_LAYER),
+                    ),
+                    ..default()
+                },
+                Effect {
+                    frame: 0,
+                    frames: sheet.frames,
+                    timer: Timer::from_seconds(sheet.frame_seconds, TimerMode::Repeating),
+                },
+                grid_pos,
+            ))
+            .id()
+    }
+}
+
+/// Steps effects through their frames on real time, so they play through a
+/// hit-stop.
+pub fn animate_effects_system(
+    time: Res<Time>,
+    mut effects: Query<(Entity, &mut Effect, &mut TextureAtlasSprite)>,
+    mut commands: Commands,
+) {
+    for (entity, mut effect, mut sprite) in &mut effects {
+        effect.timer.tick(time.raw_delta());
+        effect.frame += effect.timer.times_finished_this_tick() as usize;
+
+        if effect.frame >= effect.frames {
+            commands.entity(entity).despawn();
+        } else {
+            sprite.index = effect.frame;
+        }
+    }
+}
+
+/// Pauses game time while a hit-stop lasts.
+pub fn hit_stop_system(mut time: ResMut<Time>, mut hit_stop: ResMut<HitStop>) {
+    let raw_delta = time.raw_delta();
+    hit_stop.timer.tick(raw_delta);
+
+    let frozen = !hit_stop.timer.finished();
+    if frozen != time.is_paused() {
+        if frozen {
+            time.pause();
+        } else {
+            time.unpause();
+        }
+    }
+}
+
+/// Jitters the camera around where it rests while a shake lasts, easing off
+/// towards the end.
+pub fn screen_shake_system(
+    time: Res<Time>,
+    mut shake: ResMut<ScreenShake>,
+    mut rest: Local<Option<Vec3>>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    let Ok(mut transform) = cameras.get_single_mut() else {
+        return;
+    };
+
+    shake.timer.tick(time.raw_delta());
+    if shake.timer.finished() {
+        if let Some(rest) = rest.take() {
+            transform.translation = rest;
+            shake.strength = 0.0;
+        }
+        return;
+    }
+
+    let rest = *rest.get_or_insert(transform.translation);
+    let t = time.raw_elapsed_seconds();
+    let fade = 1.0 - shake.timer.percent();
+    let offset = Vec2::new((t * 90.0).sin(), (t * 70.0).cos()) * shake.strength * fade;
+    transform.translation = rest + offset.extend(0.0);
+}
+
+/// Flashes the critical hit effect over every unit struck by a critical.
+pub fn critical_hit_effect_system(
+    mut events: EventReader<CombatResolved>,
+    mut effects: EffectSpawner,
+) {
+    for event in events.iter() {
+        for strike in event
+            .outcome
+            .strikes
+            .iter()
+            .filter(|strike| strike.critical)
+        {
+            let target = match strike.side {
+                Side::Attacker => event.defender_pos,
+                Side::Defender => event.attacker_pos,
+            };
+            effects.spawn_effect(EffectId::CriticalHit, target);
+        }
+    }
+}
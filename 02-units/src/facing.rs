@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::grid::GridPos;
+
+/// The direction a unit looks in. It picks the block of the unit's sheet
+/// that is drawn, mirrored for `Left`.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum Facing {
+    Left,
+    #[default]
+    Right,
+    Up,
+    Down,
+}
+
+impl Facing {
+    pub const ALL: [Facing; 4] = [Facing::Left, Facing::Right, Facing::Up, Facing::Down];
+
+    /// The facing of someone on `from` looking at `to`, preferring left or
+    /// right on diagonals. `None` if both are the same tile.
+    pub fn toward(from: GridPos, to: GridPos) -> Option<Facing> {
+        let dx = to.x - from.x;
+        let dy = to.y - from.y;
+
+        if dx == 0 && dy == 0 {
+            None
+        } else if dx.abs() >= dy.abs() {
+            Some(if dx > 0 { Facing::Right } else { Facing::Left })
+        } else {
+            Some(if dy > 0 { Facing::Up } else { Facing::Down })
+        }
+    }
+}
+
+/// Turns a unit toward a tile, e.g. the one it is attacking.
+pub struct FaceTile {
+    pub entity: Entity,
+    pub target: GridPos,
+}
+
+pub fn face_tile_system(
+    mut events: EventReader<FaceTile>,
+    mut units: Query<(&GridPos, &mut Facing)>,
+) {
+    for event in events.iter() {
+        let Ok((grid_pos, mut facing)) = units.get_mut(event.entity) else {
+            continue;
+        };
+        if let Some(new_facing) = Facing::toward(*grid_pos, event.target) {
+            facing.set_if_neq(new_facing);
+        }
+    }
+}
+
+/// Turns units the way they last moved.
+pub fn face_movement_system(
+    mut last_positions: Local<HashMap<Entity, GridPos>>,
+    mut units: Query<(Entity, &GridPos, &mut Facing), Changed<GridPos>>,
+    mut removed: RemovedComponents<Facing>,
+) {
+    for entity in removed.iter() {
+        last_positions.remove(&entity);
+    }
+
+    for (entity, grid_pos, mut facing) in &mut units {
+        let last_position = last_positions.insert(entity, *grid_pos);
+        if let Some(new_facing) =
+            last_position.and_then(|last_position| Facing::toward(last_position, *grid_pos))
+        {
+            facing.set_if_neq(new_facing);
+        }
+    }
+}
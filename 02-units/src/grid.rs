@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::battlefield::Battlefield;
+
+/// A tile coordinate on the battlefield, with the origin at the bottom-left
+/// tile. Coordinates may be negative or past the edge of the map while doing
+/// arithmetic; `Battlefield::contains` tells whether they are on it.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub struct GridPos {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl GridPos {
+    pub const fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn offset(self, dx: i32, dy: i32) -> Self {
+        Self::new(self.x + dx, self.y + dy)
+    }
+
+    /// The four orthogonally adjacent positions.
+    pub fn neighbours(self) -> [GridPos; 4] {
+        [
+            self.offset(0, 1),
+            self.offset(1, 0),
+            self.offset(0, -1),
+            self.offset(-1, 0),
+        ]
+    }
+
+    /// Number of orthogonal steps between two positions.
+    pub fn distance(self, other: GridPos) -> u32 {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+
+    /// Every position between `min_distance` and `max_distance` steps away,
+    /// both included.
+    pub fn within(self, min_distance: u32, max_distance: u32) -> impl Iterator<Item = GridPos> {
+        let reach = max_distance as i32;
+
+        (-reach..=reach)
+            .flat_map(move |dy| (-reach..=reach).map(move |dx| self.offset(dx, dy)))
+            .filter(move |other| (min_distance..=max_distance).contains(&self.distance(*other)))
+    }
+}
+
+/// Moves entities to the tile in their `GridPos` whenever it changes, keeping
+/// the layer they were spawned on.
+pub fn sync_grid_transform_system(
+    battlefield: Res<Battlefield>,
+    mut query: Query<(&GridPos, &mut Transform), Changed<GridPos>>,
+) {
+    for (grid_pos, mut transform) in &mut query {
+        transform.translation = battlefield.grid_to_world(*grid_pos, transform.translation.z);
+    }
+}
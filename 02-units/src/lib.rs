@@ -0,0 +1,27 @@
+pub mod animation;
+pub mod atlas;
+pub mod autotile;
+pub mod battlefield;
+pub mod class;
+pub mod combat;
+pub mod cursor;
+pub mod effect;
+pub mod facing;
+pub mod grid;
+pub mod map;
+pub mod movement;
+pub mod overlay;
+pub mod palette;
+pub mod pathfinding;
+pub mod picking;
+pub mod scenario;
+pub mod selection;
+pub mod terrain;
+pub mod tiled;
+pub mod tilemap;
+pub mod tileset;
+pub mod turn;
+pub mod unit;
+pub mod walk;
+pub mod water;
+pub mod weapon;
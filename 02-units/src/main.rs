@@ -1,446 +1,56 @@
 use bevy::{prelude::*, window::WindowResolution};
 
-type TilemapDimensions = [[Tile; 13]; 6];
-
-struct Tilemap {
-    data: TilemapDimensions,
-    num_columns: usize,
-    num_rows: usize,
-}
-
-impl Tilemap {
-    pub fn new(data: TilemapDimensions) -> Self {
-        let num_columns = data.get(0).unwrap().len();
-        let num_rows = data.len();
-
-        Self {
-            data,
-            num_columns,
-            num_rows,
-        }
-    }
-}
-
-#[derive(Resource)]
-struct Battlefield {
-    tile_size: f32,
-    tilemap: Tilemap,
-}
-
-impl Battlefield {
-    pub fn default() -> Self {
-        Self {
-            tile_size: 16.0,
-            tilemap: Tilemap::new([
-                [
-                    Tile::from_type(TileType::Brown1),
-                    Tile::from_type(TileType::BrownGreenLower1),
-                    Tile::from_type(TileType::BrownGreenLower2),
-                    Tile::from_type(TileType::BrownGreenLower2),
-                    Tile::from_type(TileType::BrownGreenLower3),
-                    Tile::from_type(TileType::BrownGreenLower5),
-                    Tile::from_type(TileType::Brown2),
-                    Tile::from_type(TileType::BrownGreenLower1),
-                    Tile::from_type(TileType::BrownGreenLower3),
-                    Tile::from_type(TileType::BrownGreenLower5),
-                    Tile::from_type(TileType::BrownGreenLower1),
-                    Tile::from_type(TileType::BrownGreenLower3),
-                    Tile::from_type(TileType::Brown4),
-                ],
-                [
-                    Tile::from_type(TileType::Brown3),
-                    Tile::from_type(TileType::BrownGreenMiddle1),
-                    Tile::from_type(TileType::Green2),
-                    Tile::from_type(TileType::BrownGreenUpper2),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::Green3),
-                    Tile::from_type(TileType::BrownGreenLower2),
-                    Tile::from_type(TileType::Green3),
-                    Tile::from_type(TileType::Green2),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::BrownGreenMiddle3),
-                    Tile::from_type(TileType::BrownGreenMiddle1),
-                    Tile::from_type(TileType::BrownGreenLower3),
-                ],
-                [
-                    Tile::from_type(TileType::BrownGreenMiddle4),
-                    Tile::from_type(TileType::Green3),
-                    Tile::from_type(TileType::BrownGreenMiddle3),
-                    Tile::from_type(TileType::BrownGreenLower5),
-                    Tile::from_type(TileType::BrownGreenMiddle1),
-                    Tile::from_type(TileType::Green3),
-                    Tile::from_type(TileType::Green4),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::Green2),
-                    Tile::from_type(TileType::Green3),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::Green2),
-                    Tile::from_type(TileType::BrownGreenUpper3),
-                ],
-                [
-                    Tile::from_type(TileType::BrownGreenMiddle4),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::Green3),
-                    Tile::from_type(TileType::Green4),
-                    Tile::from_type(TileType::Green2),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::BrownGreenMiddle3),
-                    Tile::from_type(TileType::BrownGreenUpper7),
-                    Tile::from_type(TileType::BrownGreenUpper1),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::Green3),
-                    Tile::from_type(TileType::BrownGreenMiddle6),
-                ],
-                [
-                    Tile::from_type(TileType::Brown2),
-                    Tile::from_type(TileType::BrownGreenUpper1),
-                    Tile::from_type(TileType::Green4),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::Green3),
-                    Tile::from_type(TileType::Green2),
-                    Tile::from_type(TileType::BrownGreenUpper2),
-                    Tile::from_type(TileType::Green1),
-                    Tile::from_type(TileType::Green3),
-                    Tile::from_type(TileType::BrownGreenLower2),
-                    Tile::from_type(TileType::Green4),
-                    Tile::from_type(TileType::Green2),
-                    Tile::from_type(TileType::BrownGreenMiddle6),
-                ],
-                [
-                    Tile::from_type(TileType::Brown1),
-                    Tile::from_type(TileType::Brown4),
-                    Tile::from_type(TileType::BrownGreenUpper5),
-                    Tile::from_type(TileType::BrownGreenUpper1),
-                    Tile::from_type(TileType::BrownGreenUpper3),
-                    Tile::from_type(TileType::BrownGreenUpper1),
-                    Tile::from_type(TileType::BrownGreenLower7),
-                    Tile::from_type(TileType::BrownGreenUpper3),
-                    Tile::from_type(TileType::BrownGreenUpper5),
-                    Tile::from_type(TileType::BrownGreenUpper1),
-                    Tile::from_type(TileType::BrownGreenUpper2),
-                    Tile::from_type(TileType::BrownGreenUpper3),
-                    Tile::from_type(TileType::Brown2),
-                ],
-            ]),
-        }
-    }
-
-    pub fn to_battlefield_coordinates(&self, x: f32, y: f32, z: f32) -> Vec3 {
-        let half_tile_size: f32 = self.tile_size / 2.0;
-        let half_battlefield_width_in_pixels: f32 =
-            self.tilemap.num_columns as f32 * self.tile_size / 2.0;
-        let half_battlefield_height_in_pixels: f32 =
-            self.tilemap.num_rows as f32 * self.tile_size / 2.0;
-        let width_center_offset: f32 = half_battlefield_width_in_pixels - half_tile_size;
-        let height_center_offset: f32 = half_battlefield_height_in_pixels - half_tile_size;
-
-        Vec3::new(x - width_center_offset, y - height_center_offset, z)
-    }
-}
-
-const BATTLEFIELD_NUM_COLUMNS: usize = 20;
-const BATTLEFIELD_NUM_ROWS: usize = 20;
-
-enum TileType {
-    Brown1,
-    Brown2,
-    Brown3,
-    Brown4,
-    Green1,
-    Green2,
-    Green3,
-    Green4,
-    BrownGreenUpper1,
-    BrownGreenUpper2,
-    BrownGreenUpper3,
-    BrownGreenUpper5,
-    BrownGreenUpper7,
-    BrownGreenMiddle1,
-    BrownGreenMiddle3,
-    BrownGreenMiddle4,
-    BrownGreenMiddle6,
-    BrownGreenLower1,
-    BrownGreenLower2,
-    BrownGreenLower3,
-    BrownGreenLower5,
-    BrownGreenLower7,
-}
-
-struct Tile {
-    index: usize,
-}
-
-impl Tile {
-    fn from_type(tile_type: TileType) -> Tile {
-        match tile_type {
-            TileType::Brown1 => Tile { index: 0 },
-            TileType::Brown2 => Tile { index: 1 },
-            TileType::Brown3 => Tile { index: 2 },
-            TileType::Brown4 => Tile { index: 3 },
-            TileType::Green1 => Tile {
-                index: 2 * BATTLEFIELD_NUM_COLUMNS,
-            },
-            TileType::Green2 => Tile {
-                index: 2 * BATTLEFIELD_NUM_COLUMNS + 1,
-            },
-            TileType::Green3 => Tile {
-                index: 2 * BATTLEFIELD_NUM_COLUMNS + 2,
-            },
-            TileType::Green4 => Tile {
-                index: 2 * BATTLEFIELD_NUM_COLUMNS + 3,
-            },
-            TileType::BrownGreenUpper1 => Tile {
-                index: 7 * BATTLEFIELD_NUM_COLUMNS,
-            },
-            TileType::BrownGreenUpper2 => Tile {
-                index: 7 * BATTLEFIELD_NUM_COLUMNS + 1,
-            },
-            TileType::BrownGreenUpper3 => Tile {
-                index: 7 * BATTLEFIELD_NUM_COLUMNS + 2,
-            },
-            TileType::BrownGreenUpper5 => Tile {
-                index: 7 * BATTLEFIELD_NUM_COLUMNS + 4,
-            },
-            TileType::BrownGreenUpper7 => Tile {
-                index: 7 * BATTLEFIELD_NUM_COLUMNS + 6,
-            },
-            TileType::BrownGreenMiddle1 => Tile {
-                index: 8 * BATTLEFIELD_NUM_COLUMNS,
-            },
-            TileType::BrownGreenMiddle3 => Tile {
-                index: 8 * BATTLEFIELD_NUM_COLUMNS + 2,
-            },
-            TileType::BrownGreenMiddle4 => Tile {
-                index: 8 * BATTLEFIELD_NUM_COLUMNS + 3,
-            },
-            TileType::BrownGreenMiddle6 => Tile {
-                index: 8 * BATTLEFIELD_NUM_COLUMNS + 5,
-            },
-            TileType::BrownGreenLower1 => Tile {
-                index: 9 * BATTLEFIELD_NUM_COLUMNS,
-            },
-            TileType::BrownGreenLower2 => Tile {
-                index: 9 * BATTLEFIELD_NUM_COLUMNS + 1,
-            },
-            TileType::BrownGreenLower3 => Tile {
-                index: 9 * BATTLEFIELD_NUM_COLUMNS + 2,
-            },
-            TileType::BrownGreenLower5 => Tile {
-                index: 9 * BATTLEFIELD_NUM_COLUMNS + 4,
-            },
-            TileType::BrownGreenLower7 => Tile {
-                index: 9 * BATTLEFIELD_NUM_COLUMNS + 6,
-            },
-        }
-    }
-}
-
-fn create_battlefield_system(
-    battlefield: Res<Battlefield>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) {
-    commands.spawn(Camera2dBundle::default());
-
-    let tiles_handle = asset_server.load("Tiles/FullTileset.png");
-    let tiles_atlas = TextureAtlas::from_grid(
-        tiles_handle,
-        Vec2::new(battlefield.tile_size, battlefield.tile_size),
-        BATTLEFIELD_NUM_COLUMNS,
-        BATTLEFIELD_NUM_ROWS,
-        None,
-        None,
-    );
-    let tiles_atlas_handle = texture_atlases.add(tiles_atlas);
-
-    for (y, row) in battlefield.tilemap.data.iter().enumerate() {
-        for (x, col) in row.iter().enumerate() {
-            commands.spawn(SpriteSheetBundle {
-                texture_atlas: tiles_atlas_handle.clone(),
-                sprite: TextureAtlasSprite::new(col.index),
-                transform: Transform {
-                    translation: battlefield.to_battlefield_coordinates(
-                        x as f32 * battlefield.tile_size,
-                        y as f32 * battlefield.tile_size,
-                        0.0,
-                    ),
-                    ..default()
-                },
-                ..default()
-            });
-        }
-    }
-}
-
-fn load_unit(
-    sprite_sheet: &str,
-    asset_server: &Res<AssetServer>,
-    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
-) -> Handle<TextureAtlas> {
-    const SPRITE_SIZE: f32 = 32.0;
-    const NUM_COLUMNS: usize = 4;
-    const NUM_ROWS: usize = 4;
-
-    let sprite_handle = asset_server.load(sprite_sheet);
-    let texture_atlas = TextureAtlas::from_grid(
-        sprite_handle,
-        Vec2::new(SPRITE_SIZE, SPRITE_SIZE),
-        NUM_COLUMNS,
-        NUM_ROWS,
-        None,
-        None,
-    );
-
-    return texture_atlases.add(texture_atlas);
-}
-
-fn spawn_unit(
-    atlas_handle: Handle<TextureAtlas>,
-    column_in_battlefield: usize,
-    row_in_battlefield: usize,
-    flip: bool,
-    battlefield: &Res<Battlefield>,
-    commands: &mut Commands,
-) {
-    const SPRITE_SIZE: f32 = 32.0;
-
-    commands.spawn(SpriteSheetBundle {
-        texture_atlas: atlas_handle,
-        sprite: TextureAtlasSprite::new(0),
-        transform: Transform {
-            translation: battlefield.to_battlefield_coordinates(
-                column_in_battlefield as f32 * SPRITE_SIZE + SPRITE_SIZE / 4.0,
-                row_in_battlefield as f32 * SPRITE_SIZE + SPRITE_SIZE / 4.0,
-                1.0,
-            ),
-            rotation: if flip {
-                Quat::from_rotation_y(std::f32::consts::PI)
-            } else {
-                Quat::default()
-            },
-            ..default()
-        },
-        ..default()
-    });
-}
-
-fn create_units_system(
-    battlefield: Res<Battlefield>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) {
-    spawn_unit(
-        load_unit(
-            "Sprite Sheets/Archer/Archer_Blue1.png",
-            &asset_server,
-            &mut texture_atlases,
-        ),
-        0,
-        0,
-        false,
-        &battlefield,
-        &mut commands,
-    );
-
-    spawn_unit(
-        load_unit(
-            "Sprite Sheets/Wizard/Wizard_Blue3.png",
-            &asset_server,
-            &mut texture_atlases,
-        ),
-        1,
-        1,
-        false,
-        &battlefield,
-        &mut commands,
-    );
-
-    spawn_unit(
-        load_unit(
-            "Sprite Sheets/LanceKnight/LanceKnight_Blue.png",
-            &asset_server,
-            &mut texture_atlases,
-        ),
-        2,
-        2,
-        false,
-        &battlefield,
-        &mut commands,
-    );
-
-    spawn_unit(
-        load_unit(
-            "Sprite Sheets/SwordFighter/SwordFighter_LongHair_Blue1.png",
-            &asset_server,
-            &mut texture_atlases,
-        ),
-        2,
-        0,
-        false,
-        &battlefield,
-        &mut commands,
-    );
-
-    spawn_unit(
-        load_unit(
-            "Sprite Sheets/Archer/Archer_Red1.png",
-            &asset_server,
-            &mut texture_atlases,
-        ),
-        5,
-        1,
-        true,
-        &battlefield,
-        &mut commands,
-    );
-
-    spawn_unit(
-        load_unit(
-            "Sprite Sheets/Wizard/Wizard_Red3.png",
-            &asset_server,
-            &mut texture_atlases,
-        ),
-        5,
-        0,
-        true,
-        &battlefield,
-        &mut commands,
-    );
-
-    spawn_unit(
-        load_unit(
-            "Sprite Sheets/LanceKnight/LanceKnight_Red.png",
-            &asset_server,
-            &mut texture_atlases,
-        ),
-        4,
-        2,
-        true,
-        &battlefield,
-        &mut commands,
-    );
-
-    spawn_unit(
-        load_unit(
-            "Sprite Sheets/SwordFighter/SwordFighter_LongHair_Red1.png",
-            &asset_server,
-            &mut texture_atlases,
-        ),
-        3,
-        1,
-        true,
-        &battlefield,
-        &mut commands,
-    );
-}
+use strategy_game_rs::{
+    animation::{animate_units_system, UnitAnimations, UnitAnimationsLoader},
+    atlas::UnitAtlasCache,
+    battlefield::{create_battlefield_system, load_battlefield_map_system, Battlefield},
+    class::{
+        class_definitions_loaded, load_class_definitions_system, reload_class_definitions_system,
+        ClassDefinition, ClassDefinitionLoader,
+    },
+    combat::{
+        attack_after_walk_system, attack_system, remove_fallen_units_system, AttackOrder,
+        CombatResolved, CombatRng,
+    },
+    cursor::{
+        follow_hovered_tile_system, grid_cursor_action_system, move_grid_cursor_system,
+        spawn_grid_cursor_system,
+    },
+    effect::{
+        animate_effects_system, critical_hit_effect_system, hit_stop_system, screen_shake_system,
+        EffectAtlases, HitStop, ScreenShake,
+    },
+    facing::{face_movement_system, face_tile_system, FaceTile},
+    grid::sync_grid_transform_system,
+    map::{BattlefieldMap, MapAsset, MapAssetLoader},
+    movement::MoveRules,
+    overlay::{
+        animate_overlay_tiles_system, apply_overlay_events_system, load_overlay_sheets_system,
+        preview_path_system, show_danger_zone_system, show_selection_range_system,
+        toggle_danger_zone_system, DangerZone, OverlayEvent,
+    },
+    palette::{generate_palette_swaps_system, Palette, PaletteLoader, PaletteSwaps},
+    picking::{mouse_picking_system, HoveredTile, SelectionCancelled, TileHovered, TileSelected},
+    scenario::{
+        load_scenario_system, spawn_scenario_system, ActiveScenario, Scenario, ScenarioDeployed,
+        ScenarioLoader,
+    },
+    selection::{cancel_selection_system, clear_selection_system, select_unit_system},
+    tiled::TiledMapLoader,
+    tileset::{Tileset, TilesetLoader},
+    turn::{
+        check_battle_end_system, end_phase_when_done_system, end_player_phase_system,
+        finish_deployment_system, is_phase, pass_computer_phase_system, start_phase_system,
+        BattleState, PhaseStarted, TurnCounter,
+    },
+    walk::{order_move_system, walk_system, UnitMoved},
+    water::{animate_water_system, WaterAnimation},
+    weapon::{armory_loaded, load_armory_system, Armory, ArmoryLoader},
+};
 
 fn main() {
     App::new()
         .insert_resource(Msaa::Off)
-        .insert_resource(Battlefield::default())
         .add_plugins(
             DefaultPlugins
                 .set(WindowPlugin {
@@ -452,9 +62,170 @@ fn main() {
                     }),
                     ..default()
                 })
+                .set(AssetPlugin {
+                    watch_for_changes: true,
+                    ..default()
+                })
                 .set(ImagePlugin::default_nearest()),
         )
-        .add_startup_system(create_battlefield_system)
-        .add_startup_system(create_units_system)
+        .add_asset::<MapAsset>()
+        .init_asset_loader::<MapAssetLoader>()
+        .init_asset_loader::<TiledMapLoader>()
+        .add_asset::<Tileset>()
+        .init_asset_loader::<TilesetLoader>()
+        .add_asset::<UnitAnimations>()
+        .init_asset_loader::<UnitAnimationsLoader>()
+        .add_asset::<ClassDefinition>()
+        .init_asset_loader::<ClassDefinitionLoader>()
+        .add_asset::<Scenario>()
+        .init_asset_loader::<ScenarioLoader>()
+        .add_asset::<Palette>()
+        .init_asset_loader::<PaletteLoader>()
+        .add_asset::<Armory>()
+        .init_asset_loader::<ArmoryLoader>()
+        .init_resource::<PaletteSwaps>()
+        .add_event::<TileHovered>()
+        .add_event::<TileSelected>()
+        .add_event::<SelectionCancelled>()
+        .init_resource::<HoveredTile>()
+        .init_resource::<UnitAtlasCache>()
+        .add_state::<BattleState>()
+        .init_resource::<TurnCounter>()
+        .add_event::<PhaseStarted>()
+        .add_startup_system(load_battlefield_map_system)
+        .add_startup_system(load_class_definitions_system)
+        .add_startup_system(load_scenario_system)
+        .add_startup_system(load_armory_system)
+        .add_system(
+            create_battlefield_system
+                .run_if(resource_exists::<BattlefieldMap>())
+                .run_if(not(resource_exists::<Battlefield>())),
+        )
+        .add_system(
+            spawn_scenario_system
+                .in_set(OnUpdate(BattleState::Deployment))
+                .run_if(resource_exists::<ActiveScenario>())
+                .run_if(resource_exists::<Battlefield>())
+                .run_if(class_definitions_loaded)
+                .run_if(armory_loaded)
+                .run_if(not(resource_exists::<ScenarioDeployed>())),
+        )
+        .add_system(
+            finish_deployment_system
+                .in_set(OnUpdate(BattleState::Deployment))
+                .run_if(resource_exists::<ScenarioDeployed>()),
+        )
+        .add_system(start_phase_system.in_schedule(OnEnter(BattleState::PlayerPhase)))
+        .add_system(start_phase_system.in_schedule(OnEnter(BattleState::EnemyPhase)))
+        .add_system(start_phase_system.in_schedule(OnEnter(BattleState::OtherFactionPhase)))
+        .add_system(end_player_phase_system.in_set(OnUpdate(BattleState::PlayerPhase)))
+        .add_system(
+            pass_computer_phase_system
+                .run_if(
+                    in_state(BattleState::EnemyPhase)
+                        .or_else(in_state(BattleState::OtherFactionPhase)),
+                )
+                .before(end_phase_when_done_system),
+        )
+        .add_system(end_phase_when_done_system.run_if(is_phase))
+        .add_system(
+            check_battle_end_system
+                .run_if(is_phase)
+                .after(end_phase_when_done_system),
+        )
+        .init_resource::<MoveRules>()
+        .add_systems(
+            (select_unit_system, cancel_selection_system)
+                .distributive_run_if(resource_exists::<Battlefield>())
+                .distributive_run_if(in_state(BattleState::PlayerPhase))
+                .after(mouse_picking_system)
+                .after(grid_cursor_action_system),
+        )
+        .add_event::<UnitMoved>()
+        .init_resource::<CombatRng>()
+        .add_event::<CombatResolved>()
+        .add_event::<AttackOrder>()
+        .add_system(
+            order_move_system
+                .before(select_unit_system)
+                .after(mouse_picking_system)
+                .after(grid_cursor_action_system)
+                .run_if(resource_exists::<Battlefield>())
+                .run_if(in_state(BattleState::PlayerPhase)),
+        )
+        .add_system(attack_after_walk_system.after(walk_system))
+        .add_system(
+            attack_system
+                .after(order_move_system)
+                .after(attack_after_walk_system)
+                .run_if(resource_exists::<Battlefield>()),
+        )
+        .add_system(remove_fallen_units_system.after(animate_units_system))
+        .init_resource::<EffectAtlases>()
+        .init_resource::<ScreenShake>()
+        .init_resource::<HitStop>()
+        .add_system(hit_stop_system.in_base_set(CoreSet::PreUpdate))
+        .add_system(
+            critical_hit_effect_system
+                .after(attack_system)
+                .run_if(resource_exists::<Battlefield>()),
+        )
+        .add_system(animate_effects_system)
+        .add_system(screen_shake_system)
+        .add_event::<OverlayEvent>()
+        .init_resource::<DangerZone>()
+        .add_startup_system(load_overlay_sheets_system)
+        .add_systems(
+            (
+                show_selection_range_system
+                    .after(select_unit_system)
+                    .after(order_move_system),
+                preview_path_system
+                    .after(show_selection_range_system)
+                    .after(follow_hovered_tile_system)
+                    .after(move_grid_cursor_system),
+                toggle_danger_zone_system,
+                show_danger_zone_system
+                    .after(toggle_danger_zone_system)
+                    .after(walk_system)
+                    .after(attack_system),
+                apply_overlay_events_system
+                    .after(show_selection_range_system)
+                    .after(preview_path_system)
+                    .after(show_danger_zone_system),
+            )
+                .distributive_run_if(resource_exists::<Battlefield>()),
+        )
+        .add_system(animate_overlay_tiles_system)
+        .add_system(
+            walk_system
+                .after(sync_grid_transform_system)
+                .before(face_movement_system)
+                .run_if(resource_exists::<Battlefield>()),
+        )
+        .add_system(clear_selection_system.in_schedule(OnExit(BattleState::PlayerPhase)))
+        .add_system(reload_class_definitions_system)
+        .add_event::<FaceTile>()
+        .add_system(face_tile_system.before(animate_units_system))
+        .add_system(face_movement_system.before(animate_units_system))
+        .add_system(animate_units_system)
+        .add_system(generate_palette_swaps_system)
+        .add_system(animate_water_system.run_if(resource_exists::<WaterAnimation>()))
+        .add_system(sync_grid_transform_system.run_if(resource_exists::<Battlefield>()))
+        .add_system(spawn_grid_cursor_system.run_if(resource_added::<Battlefield>()))
+        .add_systems(
+            (
+                mouse_picking_system,
+                move_grid_cursor_system,
+                grid_cursor_action_system,
+            )
+                .distributive_run_if(resource_exists::<Battlefield>())
+                .distributive_run_if(in_state(BattleState::PlayerPhase)),
+        )
+        .add_system(
+            follow_hovered_tile_system
+                .after(mouse_picking_system)
+                .run_if(resource_exists::<Battlefield>()),
+        )
         .run();
 }
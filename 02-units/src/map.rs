@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, Error, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{
+    autotile::GroundLayout,
+    tilemap::TileType,
+    tileset::{Tileset, DEFAULT_TILESET},
+};
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "3f0d5a7e-2c1b-4f9a-8d36-b5e0c4a1f27d"]
+pub struct MapAsset {
+    pub name: String,
+    pub author: String,
+    pub tile_size: f32,
+    #[serde(default = "default_tileset")]
+    pub tileset_path: String,
+    #[serde(skip)]
+    pub tileset: Handle<Tileset>,
+    /// Tile rows as drawn on screen, top row first.
+    #[serde(default)]
+    pub tiles: Vec<Vec<TileType>>,
+    /// Ground to autotile into `tiles` when the map is loaded, instead of
+    /// listing every tile by hand.
+    #[serde(default)]
+    pub ground: Option<GroundLayout>,
+    #[serde(default)]
+    pub spawn_points: Vec<SpawnPoint>,
+    #[serde(default)]
+    pub rules: BattleRules,
+}
+
+fn default_tileset() -> String {
+    DEFAULT_TILESET.to_string()
+}
+
+/// A named position where a unit can be deployed. Rows are counted from the
+/// bottom of the map, like in `Tilemap`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnPoint {
+    pub name: String,
+    pub kind: String,
+    pub column: usize,
+    pub row: usize,
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct BattleRules {
+    pub objective: Option<String>,
+    pub turn_limit: Option<u32>,
+    /// Any other rule set by the map, kept verbatim for game modes to read.
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+impl MapAsset {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.tile_size <= 0.0 {
+            return Err(Error::msg(format!(
+                "map \"{}\" has an invalid tile size {}",
+                self.name, self.tile_size
+            )));
+        }
+
+        let num_columns = self.tiles.first().map_or(0, Vec::len);
+        if num_columns == 0 || self.tiles.iter().any(|row| row.len() != num_columns) {
+            return Err(Error::msg(format!(
+                "map \"{}\" must have at least one tile and rows of equal length",
+                self.name
+            )));
+        }
+
+        let num_rows = self.tiles.len();
+        if let Some(spawn_point) = self
+            .spawn_points
+            .iter()
+            .find(|spawn_point| spawn_point.column >= num_columns || spawn_point.row >= num_rows)
+        {
+            return Err(Error::msg(format!(
+                "spawn point \"{}\" of map \"{}\" is outside the map",
+                spawn_point.name, self.name
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Finishes loading a map, making its tileset a dependency of it.
+    pub fn set_default_asset(mut self, load_context: &mut LoadContext) {
+        let tileset_path = AssetPath::from(self.tileset_path.as_str()).to_owned();
+        self.tileset = load_context.get_handle(tileset_path.clone());
+        load_context.set_default_asset(LoadedAsset::new(self).with_dependency(tileset_path));
+    }
+}
+
+#[derive(Resource)]
+pub struct BattlefieldMap(pub Handle<MapAsset>);
+
+#[derive(Default)]
+pub struct MapAssetLoader;
+
+impl AssetLoader for MapAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let mut map: MapAsset = ron::de::from_bytes(bytes)?;
+            if let Some(ground) = map.ground.take() {
+                if !map.tiles.is_empty() {
+                    return Err(Error::msg(format!(
+                        "map \"{}\" sets both tiles and ground",
+                        map.name
+                    )));
+                }
+                map.tiles = ground.autotile()?;
+            }
+            map.validate()?;
+            map.set_default_asset(load_context);
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron"]
+    }
+}
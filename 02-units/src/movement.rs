@@ -0,0 +1,397 @@
+//! Where a unit can move this phase. The range calculation is a plain
+//! function over the map and the other units, so it runs without an `App`.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use bevy::prelude::Resource;
+
+use crate::{
+    grid::GridPos,
+    terrain::{MovementType, TerrainClass},
+    tilemap::Tilemap,
+    unit::Team,
+    weapon::Weapon,
+};
+
+/// Movement points needed to step onto a tile, `None` if the tile can't be
+/// entered or is off the map.
+pub trait MovementCosts {
+    fn movement_cost(&self, grid_pos: GridPos, movement_type: MovementType) -> Option<u32>;
+}
+
+impl MovementCosts for Tilemap {
+    fn movement_cost(&self, grid_pos: GridPos, movement_type: MovementType) -> Option<u32> {
+        let x = usize::try_from(grid_pos.x).ok()?;
+        let y = usize::try_from(grid_pos.y).ok()?;
+
+        self.terrain(x, y)?.movement_cost(movement_type)
+    }
+}
+
+/// A grid of terrain classes, bottom row first, for maps that aren't drawn
+/// with tiles.
+impl MovementCosts for [&[TerrainClass]] {
+    fn movement_cost(&self, grid_pos: GridPos, movement_type: MovementType) -> Option<u32> {
+        let row = self.get(usize::try_from(grid_pos.y).ok()?)?;
+        let class = row.get(usize::try_from(grid_pos.x).ok()?)?;
+
+        class.terrain().movement_cost(movement_type)
+    }
+}
+
+/// The unit whose range is being computed.
+#[derive(Clone, Copy, Debug)]
+pub struct Mover {
+    pub start: GridPos,
+    /// The unit's Move stat.
+    pub movement: u32,
+    pub movement_type: MovementType,
+    pub team: Team,
+}
+
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct MoveRules {
+    /// Units must stop on entering a tile next to an enemy.
+    pub zone_of_control: bool,
+}
+
+/// Every tile a unit can walk into, with the cheapest way of getting there.
+#[derive(Clone, Debug, Default)]
+pub struct MoveRange {
+    start: GridPos,
+    costs: HashMap<GridPos, u32>,
+    predecessors: HashMap<GridPos, GridPos>,
+    /// Reachable tiles with an ally on them, which can be crossed but not
+    /// stopped on.
+    occupied: HashSet<GridPos>,
+}
+
+impl MoveRange {
+    pub fn start(&self) -> GridPos {
+        self.start
+    }
+
+    /// Movement points spent reaching a tile, if it is reachable.
+    pub fn cost(&self, grid_pos: GridPos) -> Option<u32> {
+        self.costs.get(&grid_pos).copied()
+    }
+
+    /// The tile a unit steps from to reach `grid_pos` most cheaply.
+    pub fn predecessor(&self, grid_pos: GridPos) -> Option<GridPos> {
+        self.predecessors.get(&grid_pos).copied()
+    }
+
+    /// All reachable tiles, including the ones allies stand on.
+    pub fn reachable(&self) -> impl Iterator<Item = GridPos> + '_ {
+        self.costs.keys().copied()
+    }
+
+    pub fn can_stop_at(&self, grid_pos: GridPos) -> bool {
+        self.costs.contains_key(&grid_pos) && !self.occupied.contains(&grid_pos)
+    }
+
+    /// Reachable tiles the unit can end its move on, the start included.
+    pub fn destinations(&self) -> impl Iterator<Item = GridPos> + '_ {
+        self.reachable()
+            .filter(|grid_pos| !self.occupied.contains(grid_pos))
+    }
+
+    /// Tiles `weapon` reaches from any tile the unit can end its move on.
+    /// Tiles off the map are included.
+    pub fn attack_range(&self, weapon: &Weapon) -> HashSet<GridPos> {
+        self.destinations()
+            .flat_map(|grid_pos| grid_pos.within(weapon.min_range, weapon.max_range))
+            .collect()
+    }
+
+    /// The cheapest tile to end the move on with `target` in reach of
+    /// `weapon`. Staying put is free, so the start wins if it is in reach.
+    pub fn attack_position(&self, target: GridPos, weapon: &Weapon) -> Option<GridPos> {
+        self.destinations()
+            .filter(|grid_pos| weapon.in_range(grid_pos.distance(target)))
+            .min_by_key(|grid_pos| (self.costs[grid_pos], grid_pos.x, grid_pos.y))
+    }
+
+    /// The steps from the start to `destination`, both included, following
+    /// the predecessor map.
+    pub fn path_to(&self, destination: GridPos) -> Option<Vec<GridPos>> {
+        if !self.costs.contains_key(&destination) {
+            return None;
+        }
+
+        let mut path = vec![destination];
+        let mut current = destination;
+        while let Some(previous) = self.predecessor(current) {
+            path.push(previous);
+            current = previous;
+        }
+        path.reverse();
+
+        Some(path)
+    }
+}
+
+/// Flood fills the tiles `mover` can reach, cheapest first. Enemy units
+/// block their tiles and allies can be walked through. With zone of control
+/// on, entering a tile next to an enemy ends the move there. `occupants`
+/// holds the team of every other unit on the map.
+pub fn movement_range<M: MovementCosts + ?Sized>(
+    map: &M,
+    mover: &Mover,
+    occupants: &HashMap<GridPos, Team>,
+    rules: MoveRules,
+) -> MoveRange {
+    let is_enemy = |grid_pos: GridPos| {
+        occupants
+            .get(&grid_pos)
+            .is_some_and(|team| *team != mover.team)
+    };
+    let in_enemy_zone = |grid_pos: GridPos| {
+        rules.zone_of_control && grid_pos.neighbours().into_iter().any(is_enemy)
+    };
+
+    let mut range = MoveRange {
+        start: mover.start,
+        ..MoveRange::default()
+    };
+    range.costs.insert(mover.start, 0);
+
+    let mut frontier = BinaryHeap::new();
+    frontier.push(Reverse((0, mover.start.x, mover.start.y)));
+
+    while let Some(Reverse((cost, x, y))) = frontier.pop() {
+        let current = GridPos::new(x, y);
+        if range.cost(current).is_some_and(|best| cost > best) {
+            continue;
+        }
+        if current != mover.start && in_enemy_zone(current) {
+            continue;
+        }
+
+        for next in current.neighbours() {
+            if is_enemy(next) {
+                continue;
+            }
+            let Some(step_cost) = map.movement_cost(next, mover.movement_type) else {
+                continue;
+            };
+            let next_cost = cost + step_cost;
+            if next_cost > mover.movement || range.cost(next).is_some_and(|best| next_cost >= best)
+            {
+                continue;
+            }
+
+            range.costs.insert(next, next_cost);
+            range.predecessors.insert(next, current);
+            frontier.push(Reverse((next_cost, next.x, next.y)));
+        }
+    }
+
+    range.occupied = range
+        .costs
+        .keys()
+        .copied()
+        .filter(|grid_pos| *grid_pos != mover.start && occupants.contains_key(grid_pos))
+        .collect();
+
+    range
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        terrain::TerrainClass::{Grass, Water},
+        weapon::WeaponType,
+    };
+
+    fn mover(x: i32, y: i32, movement: u32) -> Mover {
+        Mover {
+            start: GridPos::new(x, y),
+            movement,
+            movement_type: MovementType::Foot,
+            team: Team::Blue,
+        }
+    }
+
+    fn open_field() -> Vec<&'static [TerrainClass]> {
+        vec![&[Grass; 7]; 7]
+    }
+
+    #[test]
+    fn range_is_a_diamond_on_open_ground() {
+        let map = open_field();
+        let range = movement_range(
+            &map[..],
+            &mover(3, 3, 2),
+            &HashMap::new(),
+            MoveRules::default(),
+        );
+
+        assert_eq!(range.reachable().count(), 13);
+        assert_eq!(range.cost(GridPos::new(3, 3)), Some(0));
+        assert_eq!(range.cost(GridPos::new(5, 3)), Some(2));
+        assert_eq!(range.cost(GridPos::new(4, 4)), Some(2));
+        assert_eq!(range.cost(GridPos::new(5, 4)), None);
+    }
+
+    #[test]
+    fn range_stays_inside_the_map() {
+        let map = open_field();
+        let range = movement_range(
+            &map[..],
+            &mover(0, 0, 2),
+            &HashMap::new(),
+            MoveRules::default(),
+        );
+
+        assert_eq!(range.reachable().count(), 6);
+        assert!(range.reachable().all(|pos| pos.x >= 0 && pos.y >= 0));
+    }
+
+    #[test]
+    fn impassable_tiles_are_walked_around() {
+        let map: Vec<&[TerrainClass]> = vec![&[Grass, Water, Grass], &[Grass, Grass, Grass]];
+        let range = movement_range(
+            &map[..],
+            &mover(0, 0, 4),
+            &HashMap::new(),
+            MoveRules::default(),
+        );
+
+        assert_eq!(range.cost(GridPos::new(1, 0)), None);
+        assert_eq!(range.cost(GridPos::new(2, 0)), Some(4));
+        assert_eq!(
+            range.path_to(GridPos::new(2, 0)),
+            Some(vec![
+                GridPos::new(0, 0),
+                GridPos::new(0, 1),
+                GridPos::new(1, 1),
+                GridPos::new(2, 1),
+                GridPos::new(2, 0),
+            ])
+        );
+    }
+
+    #[test]
+    fn allies_can_be_crossed_but_not_stopped_on() {
+        let map: Vec<&[TerrainClass]> = vec![&[Grass; 4]];
+        let occupants = HashMap::from([(GridPos::new(1, 0), Team::Blue)]);
+        let range = movement_range(&map[..], &mover(0, 0, 3), &occupants, MoveRules::default());
+
+        assert_eq!(range.cost(GridPos::new(3, 0)), Some(3));
+        assert!(!range.can_stop_at(GridPos::new(1, 0)));
+        assert!(range.can_stop_at(GridPos::new(2, 0)));
+        assert!(range.can_stop_at(GridPos::new(0, 0)));
+        assert_eq!(range.destinations().count(), 3);
+    }
+
+    #[test]
+    fn enemies_block_their_tiles() {
+        let map: Vec<&[TerrainClass]> = vec![&[Grass; 4]];
+        let occupants = HashMap::from([(GridPos::new(1, 0), Team::Red)]);
+        let range = movement_range(&map[..], &mover(0, 0, 3), &occupants, MoveRules::default());
+
+        assert_eq!(range.cost(GridPos::new(1, 0)), None);
+        assert_eq!(range.cost(GridPos::new(2, 0)), None);
+    }
+
+    #[test]
+    fn zone_of_control_stops_units_next_to_enemies() {
+        let map = open_field();
+        let occupants = HashMap::from([(GridPos::new(5, 3), Team::Red)]);
+        let rules = MoveRules {
+            zone_of_control: true,
+        };
+        let free = movement_range(&map[..], &mover(3, 3, 4), &occupants, MoveRules::default());
+        let controlled = movement_range(&map[..], &mover(3, 3, 4), &occupants, rules);
+
+        assert_eq!(free.cost(GridPos::new(6, 4)), Some(4));
+        assert_eq!(controlled.cost(GridPos::new(5, 4)), Some(3));
+        assert_eq!(controlled.cost(GridPos::new(6, 4)), None);
+        assert_eq!(controlled.cost(GridPos::new(5, 5)), Some(4));
+    }
+
+    #[test]
+    fn units_can_leave_a_zone_they_start_in() {
+        let map = open_field();
+        let occupants = HashMap::from([(GridPos::new(4, 3), Team::Red)]);
+        let rules = MoveRules {
+            zone_of_control: true,
+        };
+        let range = movement_range(&map[..], &mover(3, 3, 3), &occupants, rules);
+
+        assert_eq!(range.cost(GridPos::new(2, 3)), Some(1));
+        assert_eq!(range.cost(GridPos::new(1, 3)), Some(2));
+        assert_eq!(range.cost(GridPos::new(3, 5)), Some(2));
+    }
+
+    fn bow(min_range: u32, max_range: u32) -> Weapon {
+        Weapon {
+            name: "Bow".to_string(),
+            weapon_type: WeaponType::Bow,
+            might: 6,
+            hit: 85,
+            crit: 0,
+            weight: 5,
+            min_range,
+            max_range,
+            durability: 45,
+            effective_against: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn attack_range_adds_weapon_reach_to_movement() {
+        let map = open_field();
+        let standing = movement_range(
+            &map[..],
+            &mover(3, 3, 0),
+            &HashMap::new(),
+            MoveRules::default(),
+        );
+        let moving = movement_range(
+            &map[..],
+            &mover(3, 3, 1),
+            &HashMap::new(),
+            MoveRules::default(),
+        );
+
+        let bow_range = standing.attack_range(&bow(2, 2));
+        assert_eq!(bow_range.len(), 8);
+        assert!(!bow_range.contains(&GridPos::new(4, 3)));
+        assert!(moving
+            .attack_range(&bow(2, 2))
+            .contains(&GridPos::new(4, 3)));
+        assert_eq!(standing.attack_range(&bow(2, 3)).len(), 20);
+    }
+
+    #[test]
+    fn attack_position_prefers_the_cheapest_tile_in_reach() {
+        let map = open_field();
+        let range = movement_range(
+            &map[..],
+            &mover(3, 3, 3),
+            &HashMap::new(),
+            MoveRules::default(),
+        );
+
+        assert_eq!(
+            range.attack_position(GridPos::new(3, 5), &bow(2, 2)),
+            Some(GridPos::new(3, 3))
+        );
+        assert_eq!(
+            range.attack_position(GridPos::new(3, 4), &bow(2, 2)),
+            Some(GridPos::new(2, 3))
+        );
+        assert_eq!(
+            range
+                .attack_position(GridPos::new(3, 0), &bow(2, 2))
+                .map(|pos| range.cost(pos)),
+            Some(Some(1))
+        );
+    }
+}
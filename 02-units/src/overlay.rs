@@ -0,0 +1,507 @@
+//! Tinted tiles drawn between the tilemap and the units: where the selected
+//! unit can move and attack, the enemy danger zone and the path the unit
+//! would walk. Overlays are changed only through `OverlayEvent`s.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::{
+    battlefield::Battlefield,
+    class::{ClassDefinition, ClassDefinitions},
+    cursor::{CursorInput, GridCursor},
+    facing::Facing,
+    grid::GridPos,
+    movement::{movement_range, MoveRules, Mover},
+    selection::{occupants, SelectedUnit},
+    turn::BattleState,
+    unit::{Health, Stats, Team, Unit, UnitClass},
+    weapon::Weapon,
+};
+
+/// The lowest overlay. Each kind is drawn a little above the one before it,
+/// all below `UNIT_LAYER`.
+pub const OVERLAY_LAYER: f32 = 0.5;
+const KIND_SPACING: f32 = 0.05;
+
+const RANGE_SHEET_FRAMES: usize = 14;
+const RANGE_FRAME_SECONDS: f32 = 0.1;
+const ARROW_SHEET: &str = "UI Elements/MovementArrows.png";
+const ARROW_SHEET_COLUMNS: usize = 6;
+const ARROW_SHEET_ROWS: usize = 12;
+const OVERLAY_SPRITE_SIZE: f32 = 16.0;
+
+/// The range finder sheets have no purple, so the danger zone is a flat
+/// tint.
+const DANGER_TINT: Color = Color::rgba(0.6, 0.15, 0.85, 0.4);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OverlayKind {
+    /// Every tile an enemy could attack next turn.
+    Danger,
+    Move,
+    Attack,
+    /// The route the selected unit would walk, drawn as an arrow.
+    Path,
+}
+
+impl OverlayKind {
+    fn layer(self) -> f32 {
+        OVERLAY_LAYER + self as usize as f32 * KIND_SPACING
+    }
+
+    fn range_sheet(self) -> Option<&'static str> {
+        match self {
+            OverlayKind::Move => Some("UI Elements/RangerFinderTile_Blue_50%Opacity.png"),
+            OverlayKind::Attack => Some("UI Elements/RangerFinderTile_Red_50%Opacity.png"),
+            OverlayKind::Danger | OverlayKind::Path => None,
+        }
+    }
+}
+
+pub enum OverlayEvent {
+    /// Replaces the tiles of an overlay. Path tiles go from the start of the
+    /// route to its end.
+    Show(OverlayKind, Vec<GridPos>),
+    Clear(OverlayKind),
+}
+
+#[derive(Component)]
+pub struct OverlayTile {
+    kind: OverlayKind,
+}
+
+/// Texture atlases for the animated range tiles and the path arrows.
+#[derive(Resource)]
+pub struct OverlaySheets {
+    ranges: HashMap<OverlayKind, Handle<TextureAtlas>>,
+    arrows: Handle<TextureAtlas>,
+}
+
+pub fn load_overlay_sheets_system(
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut commands: Commands,
+) {
+    let tile_size = Vec2::splat(OVERLAY_SPRITE_SIZE);
+    let ranges = [OverlayKind::Move, OverlayKind::Attack]
+        .into_iter()
+        .filter_map(|kind| {
+            let texture_atlas = TextureAtlas::from_grid(
+                asset_server.load(kind.range_sheet()?),
+                tile_size,
+                RANGE_SHEET_FRAMES,
+                1,
+                None,
+                None,
+            );
+            Some((kind, texture_atlases.add(texture_atlas)))
+        })
+        .collect();
+    let arrows = TextureAtlas::from_grid(
+        asset_server.load(ARROW_SHEET),
+        tile_size,
+        ARROW_SHEET_COLUMNS,
+        ARROW_SHEET_ROWS,
+        None,
+        None,
+    );
+
+    commands.insert_resource(OverlaySheets {
+        ranges,
+        arrows: texture_atlases.add(arrows),
+    });
+}
+
+/// The arrow sheet piece for a tile of a path, given the tiles before and
+/// after it. `None` for a path that doesn't go anywhere.
+pub fn arrow_piece(
+    previous: Option<GridPos>,
+    current: GridPos,
+    next: Option<GridPos>,
+) -> Option<usize> {
+    let towards = |to: Option<GridPos>| to.and_then(|to| Facing::toward(current, to));
+
+    let piece = match (towards(previous), towards(next)) {
+        (None, None) => return None,
+        // Start caps, leading out of the first tile.
+        (None, Some(Facing::Right)) => 0,
+        (None, Some(Facing::Down)) => 1,
+        (None, Some(Facing::Up)) => 2,
+        (None, Some(Facing::Left)) => 3,
+        // Heads, pointing away from the tile the path came from.
+        (Some(Facing::Left), None) => 12,
+        (Some(Facing::Up), None) => 13,
+        (Some(Facing::Right), None) => 18,
+        (Some(Facing::Down), None) => 19,
+        (Some(from), Some(to)) => match [from, to] {
+            [Facing::Left, Facing::Right] | [Facing::Right, Facing::Left] => 6,
+            [Facing::Up, Facing::Down] | [Facing::Down, Facing::Up] => 7,
+            [Facing::Right, Facing::Down] | [Facing::Down, Facing::Right] => 8,
+            [Facing::Left, Facing::Down] | [Facing::Down, Facing::Left] => 9,
+            [Facing::Up, Facing::Right] | [Facing::Right, Facing::Up] => 14,
+            _ => 15,
+        },
+    };
+
+    Some(piece)
+}
+
+/// The tiles each overlay kind named in `events` should end up with, `None`
+/// for a cleared kind. Only the last event for each kind counts.
+fn latest_overlays<'a>(
+    events: impl IntoIterator<Item = &'a OverlayEvent>,
+) -> HashMap<OverlayKind, Option<&'a Vec<GridPos>>> {
+    let mut latest = HashMap::new();
+    for event in events {
+        match event {
+            OverlayEvent::Show(kind, tiles) => latest.insert(*kind, Some(tiles)),
+            OverlayEvent::Clear(kind) => latest.insert(*kind, None),
+        };
+    }
+    latest
+}
+
+/// Redraws the overlays named in this frame's events.
+pub fn apply_overlay_events_system(
+    mut events: EventReader<OverlayEvent>,
+    sheets: Res<OverlaySheets>,
+    battlefield: Res<Battlefield>,
+    tiles: Query<(Entity, &OverlayTile)>,
+    mut commands: Commands,
+) {
+    let latest = latest_overlays(events.iter());
+    if latest.is_empty() {
+        return;
+    }
+
+    for (entity, tile) in &tiles {
+        if latest.contains_key(&tile.kind) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    let tile_size = Some(Vec2::splat(battlefield.tile_size()));
+    for (kind, grid_positions) in latest {
+        let Some(grid_positions) = grid_positions else {
+            continue;
+        };
+
+        for (i, grid_pos) in grid_positions.iter().enumerate() {
+            let transform =
+                Transform::from_translation(battlefield.grid_to_world(*grid_pos, kind.layer()));
+            let mut tile = match kind {
+                OverlayKind::Danger => commands.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: DANGER_TINT,
+                        custom_size: tile_size,
+                        ..default()
+                    },
+                    transform,
+                    ..default()
+                }),
+                OverlayKind::Path => {
+                    let previous = i.checked_sub(1).map(|i| grid_positions[i]);
+                    let next = grid_positions.get(i + 1).copied();
+                    let Some(piece) = arrow_piece(previous, *grid_pos, next) else {
+                        continue;
+                    };
+                    commands.spawn(SpriteSheetBundle {
+                        texture_atlas: sheets.arrows.clone(),
+                        sprite: TextureAtlasSprite {
+                            index: piece,
+                            custom_size: tile_size,
+                            ..default()
+                        },
+                        transform,
+                        ..default()
+                    })
+                }
+                _ => commands.spawn(SpriteSheetBundle {
+                    texture_atlas: sheets.ranges[&kind].clone(),
+                    sprite: TextureAtlasSprite {
+                        custom_size: tile_size,
+                        ..default()
+                    },
+                    transform,
+                    ..default()
+                }),
+            };
+            tile.insert(OverlayTile { kind });
+        }
+    }
+}
+
+/// Shimmers the range tiles, all in step.
+pub fn animate_overlay_tiles_system(
+    time: Res<Time>,
+    mut tiles: Query<(&OverlayTile, &mut TextureAtlasSprite)>,
+) {
+    let frame = (time.elapsed_seconds() / RANGE_FRAME_SECONDS) as usize % RANGE_SHEET_FRAMES;
+
+    for (tile, mut sprite) in &mut tiles {
+        if tile.kind.range_sheet().is_some() && sprite.index != frame {
+            sprite.index = frame;
+        }
+    }
+}
+
+/// Shows the tiles the selected unit can end its move on, and the tiles only
+/// its weapon reaches, for as long as it stays selected.
+pub fn show_selection_range_system(
+    selected: Option<Res<SelectedUnit>>,
+    battlefield: Res<Battlefield>,
+    weapons: Query<&Weapon>,
+    mut shown: Local<bool>,
+    mut overlays: EventWriter<OverlayEvent>,
+) {
+    let Some(selected) = selected else {
+        if *shown {
+            overlays.send_batch(
+                [OverlayKind::Move, OverlayKind::Attack, OverlayKind::Path]
+                    .map(OverlayEvent::Clear),
+            );
+            *shown = false;
+        }
+        return;
+    };
+    if !selected.is_changed() {
+        return;
+    }
+
+    let destinations: Vec<_> = selected.range.destinations().collect();
+    let attack_only = weapons
+        .get(selected.entity)
+        .map(|weapon| {
+            selected
+                .range
+                .attack_range(weapon)
+                .into_iter()
+                .filter(|grid_pos| {
+                    battlefield.contains(*grid_pos) && !selected.range.can_stop_at(*grid_pos)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    overlays.send(OverlayEvent::Show(OverlayKind::Move, destinations));
+    overlays.send(OverlayEvent::Show(OverlayKind::Attack, attack_only));
+    *shown = true;
+}
+
+/// Draws the route the selected unit would take to the cursor, or to where
+/// it would attack from when the cursor is on an enemy in reach. The route
+/// is found the same way as the one the unit walks when ordered to move.
+#[allow(clippy::too_many_arguments)]
+pub fn preview_path_system(
+    selected: Option<Res<SelectedUnit>>,
+    cursors: Query<Ref<GridPos>, With<GridCursor>>,
+    battlefield: Res<Battlefield>,
+    class_definitions: Res<ClassDefinitions>,
+    definitions: Res<Assets<ClassDefinition>>,
+    rules: Res<MoveRules>,
+    units: Query<(&GridPos, &Team, &UnitClass, &Health, Option<&Weapon>)>,
+    mut overlays: EventWriter<OverlayEvent>,
+) {
+    let (Some(selected), Ok(cursor)) = (selected, cursors.get_single()) else {
+        return;
+    };
+    if !selected.is_changed() && !cursor.is_changed() {
+        return;
+    }
+
+    let start = selected.range.start();
+    let Ok((_, team, class, _, weapon)) = units.get(selected.entity) else {
+        return;
+    };
+    let enemy_under_cursor = units.iter().any(|(grid_pos, other_team, .., health, _)| {
+        *grid_pos == *cursor && other_team != team && !health.is_dead()
+    });
+    let destination = if enemy_under_cursor {
+        weapon.and_then(|weapon| selected.range.attack_position(*cursor, weapon))
+    } else {
+        Some(*cursor).filter(|grid_pos| selected.range.can_stop_at(*grid_pos))
+    };
+
+    let others = occupants(
+        units
+            .iter()
+            .filter(|(grid_pos, ..)| **grid_pos != start)
+            .map(|(grid_pos, team, _, health, _)| (grid_pos, team, health)),
+    );
+    let path = destination
+        .zip(class_definitions.get(*class, &definitions))
+        .and_then(|(destination, definition)| {
+            battlefield.find_path(
+                &selected.range,
+                destination,
+                definition.movement_type,
+                *team,
+                &others,
+                *rules,
+            )
+        });
+
+    overlays.send(match path {
+        Some(path) => OverlayEvent::Show(OverlayKind::Path, path.steps),
+        None => OverlayEvent::Clear(OverlayKind::Path),
+    });
+}
+
+/// Whether the danger zone is drawn. Toggled with Tab or the gamepad's
+/// north button.
+#[derive(Resource, Default)]
+pub struct DangerZone {
+    pub visible: bool,
+}
+
+pub fn toggle_danger_zone_system(input: CursorInput, mut danger_zone: ResMut<DangerZone>) {
+    if input.just_toggled_danger_zone() {
+        danger_zone.visible = !danger_zone.visible;
+    }
+}
+
+type Threat<'a> = (
+    &'a GridPos,
+    &'a Team,
+    &'a UnitClass,
+    &'a Stats,
+    &'a Health,
+    Option<&'a Weapon>,
+);
+
+/// Units that moved, took damage or changed weapons.
+type UnitChanged = Or<(Changed<GridPos>, Changed<Health>, Changed<Weapon>)>;
+
+/// Keeps the danger zone up to date with where the enemies of the player's
+/// team stand and what they carry.
+#[allow(clippy::too_many_arguments)]
+pub fn show_danger_zone_system(
+    danger_zone: Res<DangerZone>,
+    battlefield: Res<Battlefield>,
+    rules: Res<MoveRules>,
+    class_definitions: Res<ClassDefinitions>,
+    definitions: Res<Assets<ClassDefinition>>,
+    units: Query<Threat>,
+    changed: Query<(), (With<Unit>, UnitChanged)>,
+    mut overlays: EventWriter<OverlayEvent>,
+) {
+    if !danger_zone.is_changed() && (changed.is_empty() || !danger_zone.visible) {
+        return;
+    }
+    if !danger_zone.visible {
+        overlays.send(OverlayEvent::Clear(OverlayKind::Danger));
+        return;
+    }
+
+    let player = BattleState::PlayerPhase.team();
+    let mut tiles = HashSet::new();
+    for (grid_pos, team, class, stats, health, weapon) in &units {
+        let (Some(weapon), Some(definition)) =
+            (weapon, class_definitions.get(*class, &definitions))
+        else {
+            continue;
+        };
+        if Some(*team) == player || health.is_dead() {
+            continue;
+        }
+
+        let mover = Mover {
+            start: *grid_pos,
+            movement: stats.movement,
+            movement_type: definition.movement_type,
+            team: *team,
+        };
+        let others = occupants(
+            units
+                .iter()
+                .filter(|(other_pos, ..)| *other_pos != grid_pos)
+                .map(|(grid_pos, team, .., health, _)| (grid_pos, team, health)),
+        );
+        let range = movement_range(battlefield.tilemap(), &mover, &others, *rules);
+        tiles.extend(range.attack_range(weapon));
+    }
+
+    tiles.retain(|grid_pos| battlefield.contains(*grid_pos));
+    overlays.send(OverlayEvent::Show(
+        OverlayKind::Danger,
+        tiles.into_iter().collect(),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrow_pieces_follow_the_path() {
+        let path = [(0, 0), (1, 0), (2, 0), (2, 1), (1, 1)].map(|(x, y)| GridPos::new(x, y));
+        let pieces: Vec<_> = (0..path.len())
+            .map(|i| {
+                arrow_piece(
+                    i.checked_sub(1).map(|i| path[i]),
+                    path[i],
+                    path.get(i + 1).copied(),
+                )
+            })
+            .collect();
+
+        assert_eq!(pieces, [Some(0), Some(6), Some(15), Some(9), Some(18)]);
+        assert_eq!(arrow_piece(None, GridPos::new(3, 3), None), None);
+    }
+
+    #[test]
+    fn arrow_pieces_cap_both_ends_and_turn_either_way() {
+        let center = GridPos::new(5, 5);
+        let right = Some(GridPos::new(6, 5));
+        let left = Some(GridPos::new(4, 5));
+        let up = Some(GridPos::new(5, 6));
+        let down = Some(GridPos::new(5, 4));
+        let piece = |previous, next| arrow_piece(previous, center, next);
+
+        // Start caps lead out toward the next tile.
+        assert_eq!(
+            [right, down, up, left].map(|next| piece(None, next)),
+            [Some(0), Some(1), Some(2), Some(3)]
+        );
+        // Heads point away from the tile the path came from.
+        assert_eq!(
+            [left, up, right, down].map(|previous| piece(previous, None)),
+            [Some(12), Some(13), Some(18), Some(19)]
+        );
+        // Straights and corners are the same piece whichever way they're
+        // walked.
+        for (a, b, expected) in [
+            (left, right, 6),
+            (up, down, 7),
+            (right, down, 8),
+            (left, down, 9),
+            (up, right, 14),
+            (up, left, 15),
+        ] {
+            assert_eq!(piece(a, b), Some(expected));
+            assert_eq!(piece(b, a), Some(expected));
+        }
+    }
+
+    #[test]
+    fn later_overlay_events_replace_earlier_ones_of_the_same_kind() {
+        let tiles = |x| vec![GridPos::new(x, 0)];
+        let events = [
+            OverlayEvent::Show(OverlayKind::Move, tiles(1)),
+            OverlayEvent::Show(OverlayKind::Attack, tiles(2)),
+            OverlayEvent::Show(OverlayKind::Path, tiles(3)),
+            OverlayEvent::Clear(OverlayKind::Move),
+            OverlayEvent::Clear(OverlayKind::Path),
+            OverlayEvent::Show(OverlayKind::Path, tiles(4)),
+            OverlayEvent::Show(OverlayKind::Attack, tiles(5)),
+        ];
+        let latest = latest_overlays(&events);
+
+        assert_eq!(latest.len(), 3);
+        assert_eq!(latest[&OverlayKind::Move], None);
+        assert_eq!(latest[&OverlayKind::Attack], Some(&tiles(5)));
+        assert_eq!(latest[&OverlayKind::Path], Some(&tiles(4)));
+        assert!(!latest.contains_key(&OverlayKind::Danger));
+    }
+}
@@ -0,0 +1,290 @@
+//! Recolours unit sprite sheets on the CPU. Each class ships a reference
+//! sheet drawn with key colours: a blue team ramp, a green skin ramp and a
+//! magenta hair colour. Comparing it with a shipped sheet tells which colour
+//! each key stands for, and a palette file can then replace any of them.
+
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, Error, HandleId, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::TextureFormat,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+type Rgb = [u8; 3];
+
+fn parse_hex(color: &str) -> Result<Rgb, Error> {
+    let digits = color.strip_prefix('#').unwrap_or(color);
+    let channel = |i: usize| {
+        digits
+            .get(i..i + 2)
+            .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+    };
+
+    match (digits.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+        _ => Err(Error::msg(format!("'{color}' is not a #RRGGBB colour"))),
+    }
+}
+
+fn check_format(image: &Image) -> Result<(), Error> {
+    match image.texture_descriptor.format {
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm => Ok(()),
+        format => Err(Error::msg(format!(
+            "can only recolour RGBA8 images, not {format:?}"
+        ))),
+    }
+}
+
+/// Maps colours of a reference sheet to the colours they are drawn with.
+/// Colours without an entry are kept.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColorMap(HashMap<Rgb, Rgb>);
+
+impl ColorMap {
+    /// Learns the colours of `recoloured` by comparing it pixel by pixel with
+    /// the reference sheet it was painted from. Transparent pixels are
+    /// skipped, and where a key colour was painted more than one way the
+    /// first wins.
+    pub fn extract(reference: &Image, recoloured: &Image) -> Result<Self, Error> {
+        check_format(reference)?;
+        check_format(recoloured)?;
+        if reference.size() != recoloured.size() {
+            return Err(Error::msg(format!(
+                "sheet is {} but its reference is {}",
+                recoloured.size(),
+                reference.size()
+            )));
+        }
+
+        let mut colors = HashMap::new();
+        for (from, to) in reference
+            .data
+            .chunks_exact(4)
+            .zip(recoloured.data.chunks_exact(4))
+        {
+            if from[3] == 0 {
+                continue;
+            }
+            colors
+                .entry([from[0], from[1], from[2]])
+                .or_insert([to[0], to[1], to[2]]);
+        }
+
+        Ok(Self(colors))
+    }
+
+    pub fn get(&self, color: Rgb) -> Option<Rgb> {
+        self.0.get(&color).copied()
+    }
+
+    /// Replaces or adds the colours of `overrides`.
+    pub fn extend(&mut self, overrides: &ColorMap) {
+        self.0
+            .extend(overrides.0.iter().map(|(from, to)| (*from, *to)));
+    }
+
+    /// A copy of `reference` with every mapped colour replaced. Alpha is left
+    /// alone, so shadows stay translucent.
+    pub fn apply(&self, reference: &Image) -> Result<Image, Error> {
+        check_format(reference)?;
+
+        let mut image = reference.clone();
+        for pixel in image.data.chunks_exact_mut(4) {
+            if let Some(color) = self.get([pixel[0], pixel[1], pixel[2]]) {
+                pixel[..3].copy_from_slice(&color);
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawPalette {
+    name: String,
+    colors: HashMap<String, String>,
+}
+
+/// Replacement colours for the key colours of the reference sheets, read
+/// from a `*.palette.ron` file.
+#[derive(Debug, TypeUuid)]
+#[uuid = "e6b0a4d7-91c3-4f28-8d5e-3a7c12f9b046"]
+pub struct Palette {
+    pub name: String,
+    pub colors: ColorMap,
+}
+
+impl Palette {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let raw: RawPalette = ron::de::from_bytes(bytes)?;
+        let colors = raw
+            .colors
+            .iter()
+            .map(|(from, to)| Ok((parse_hex(from)?, parse_hex(to)?)))
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            name: raw.name,
+            colors: ColorMap(colors),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct PaletteLoader;
+
+impl AssetLoader for PaletteLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(Palette::parse(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["palette.ron"]
+    }
+}
+
+/// A recoloured sheet waiting for its source images and palette to load.
+struct PaletteSwapJob {
+    reference: Handle<Image>,
+    base: Handle<Image>,
+    palette: Handle<Palette>,
+    target: Handle<Image>,
+}
+
+/// Recoloured sheets, generated once per combination of reference sheet,
+/// base sheet and palette.
+#[derive(Resource, Default)]
+pub struct PaletteSwaps {
+    sheets: HashMap<(String, String, String), Handle<Image>>,
+    jobs: Vec<PaletteSwapJob>,
+}
+
+impl PaletteSwaps {
+    /// The handle of `reference` recoloured like `base`, with `palette`'s
+    /// colours on top. The image is filled in by
+    /// `generate_palette_swaps_system` once everything has loaded.
+    pub fn get_or_generate(
+        &mut self,
+        reference: &str,
+        base: &str,
+        palette: &str,
+        asset_server: &AssetServer,
+        images: &Assets<Image>,
+    ) -> Handle<Image> {
+        let key = (reference.to_string(), base.to_string(), palette.to_string());
+        if let Some(target) = self.sheets.get(&key) {
+            return target.clone();
+        }
+
+        let target = images.get_handle(HandleId::random::<Image>());
+        self.jobs.push(PaletteSwapJob {
+            reference: asset_server.load(reference),
+            base: asset_server.load(base),
+            palette: asset_server.load(palette),
+            target: target.clone(),
+        });
+        self.sheets.insert(key, target.clone());
+
+        target
+    }
+}
+
+/// Fills in recoloured sheets once their images and palette have loaded. A
+/// sheet that can't be recoloured falls back to its base sheet, so the unit
+/// is still drawn.
+pub fn generate_palette_swaps_system(
+    mut swaps: ResMut<PaletteSwaps>,
+    palettes: Res<Assets<Palette>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    swaps.jobs.retain(|job| {
+        let (Some(reference), Some(base), Some(palette)) = (
+            images.get(&job.reference),
+            images.get(&job.base),
+            palettes.get(&job.palette),
+        ) else {
+            return true;
+        };
+
+        let recoloured = ColorMap::extract(reference, base).and_then(|mut colors| {
+            colors.extend(&palette.colors);
+            colors.apply(reference)
+        });
+        let image = recoloured.unwrap_or_else(|error| {
+            error!("Could not apply the {} palette: {error}", palette.name);
+            base.clone()
+        });
+        images.set_untracked(&job.target, image);
+
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
+
+    use super::*;
+
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const SKIN: [u8; 4] = [0, 255, 0, 255];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const TAN: [u8; 4] = [210, 160, 120, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn image(pixels: [[u8; 4]; 4]) -> Image {
+        Image::new(
+            Extent3d {
+                width: 2,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            pixels.concat(),
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    #[test]
+    fn color_map_recolours_a_sheet_like_its_base() {
+        let reference = image([BLUE, SKIN, CLEAR, BLUE]);
+        let base = image([RED, TAN, TAN, RED]);
+
+        let mut colors = ColorMap::extract(&reference, &base).unwrap();
+        assert_eq!(colors.get([0, 0, 255]), Some([255, 0, 0]));
+        assert_eq!(colors.get([0, 255, 0]), Some([210, 160, 120]));
+        // Transparent pixels don't teach anything.
+        assert_eq!(colors.get([0, 0, 0]), None);
+
+        colors.extend(&ColorMap(HashMap::from([([0, 255, 0], [90, 60, 40])])));
+        let recoloured = colors.apply(&reference).unwrap();
+        assert_eq!(
+            recoloured.data,
+            [RED, [90, 60, 40, 255], CLEAR, RED].concat()
+        );
+    }
+
+    #[test]
+    fn color_map_needs_sheets_of_the_same_size() {
+        let reference = image([BLUE; 4]);
+        let mut wide = image([RED; 4]);
+        wide.resize(Extent3d {
+            width: 4,
+            height: 2,
+            depth_or_array_layers: 1,
+        });
+
+        assert!(ColorMap::extract(&reference, &wide).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{
+    battlefield::Battlefield,
+    grid::GridPos,
+    movement::{MoveRange, MoveRules, MovementCosts},
+    terrain::MovementType,
+    unit::Team,
+};
+
+/// A walkable route, start and destination included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Path {
+    pub steps: Vec<GridPos>,
+    /// Movement points spent walking it.
+    pub cost: u32,
+}
+
+/// Finds the cheapest path between two tiles with A*. Like movement ranges,
+/// enemy units block their tiles, allies can be walked through and, with
+/// zone of control on, a path can't go on past a tile next to an enemy. The
+/// destination must be free.
+pub fn find_path<M: MovementCosts + ?Sized>(
+    map: &M,
+    from: GridPos,
+    to: GridPos,
+    movement_type: MovementType,
+    team: Team,
+    occupants: &HashMap<GridPos, Team>,
+    rules: MoveRules,
+) -> Option<Path> {
+    if from != to && occupants.contains_key(&to) {
+        return None;
+    }
+
+    let is_enemy = |grid_pos: GridPos| occupants.get(&grid_pos).is_some_and(|other| *other != team);
+    let in_enemy_zone = |grid_pos: GridPos| {
+        rules.zone_of_control && grid_pos.neighbours().into_iter().any(is_enemy)
+    };
+
+    // Every step costs at least one point, so the distance never
+    // overestimates.
+    let heuristic = |grid_pos: GridPos| grid_pos.distance(to);
+
+    let mut costs = HashMap::from([(from, 0)]);
+    let mut predecessors = HashMap::new();
+    let mut open = BinaryHeap::from([Reverse((heuristic(from), 0, from.x, from.y))]);
+
+    while let Some(Reverse((_, cost, x, y))) = open.pop() {
+        let current = GridPos::new(x, y);
+        if current == to {
+            let mut steps = vec![current];
+            let mut step = current;
+            while let Some(previous) = predecessors.get(&step) {
+                steps.push(*previous);
+                step = *previous;
+            }
+            steps.reverse();
+
+            return Some(Path { steps, cost });
+        }
+        if costs.get(&current).is_some_and(|best| cost > *best) {
+            continue;
+        }
+        if current != from && in_enemy_zone(current) {
+            continue;
+        }
+
+        for next in current.neighbours() {
+            if is_enemy(next) {
+                continue;
+            }
+            let Some(step_cost) = map.movement_cost(next, movement_type) else {
+                continue;
+            };
+            let next_cost = cost + step_cost;
+            if costs.get(&next).is_some_and(|best| next_cost >= *best) {
+                continue;
+            }
+
+            costs.insert(next, next_cost);
+            predecessors.insert(next, current);
+            open.push(Reverse((
+                next_cost + heuristic(next),
+                next_cost,
+                next.x,
+                next.y,
+            )));
+        }
+    }
+
+    None
+}
+
+/// A map with every tile outside a movement range blocked off.
+struct WithinRange<'a, M: ?Sized> {
+    map: &'a M,
+    range: &'a MoveRange,
+}
+
+impl<M: MovementCosts + ?Sized> MovementCosts for WithinRange<'_, M> {
+    fn movement_cost(&self, grid_pos: GridPos, movement_type: MovementType) -> Option<u32> {
+        self.range.cost(grid_pos)?;
+        self.map.movement_cost(grid_pos, movement_type)
+    }
+}
+
+impl Battlefield {
+    /// The route a unit takes from the start of its movement range to `to`,
+    /// never leaving the range, so it can always be walked this phase.
+    pub fn find_path(
+        &self,
+        range: &MoveRange,
+        to: GridPos,
+        movement_type: MovementType,
+        team: Team,
+        occupants: &HashMap<GridPos, Team>,
+        rules: MoveRules,
+    ) -> Option<Path> {
+        let map = WithinRange {
+            map: self.tilemap(),
+            range,
+        };
+
+        find_path(
+            &map,
+            range.start(),
+            to,
+            movement_type,
+            team,
+            occupants,
+            rules,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        movement::{movement_range, Mover},
+        terrain::TerrainClass::{self, Grass, Water},
+    };
+
+    fn path(
+        map: &[&[TerrainClass]],
+        from: (i32, i32),
+        to: (i32, i32),
+        occupants: &HashMap<GridPos, Team>,
+    ) -> Option<Path> {
+        find_path(
+            map,
+            GridPos::new(from.0, from.1),
+            GridPos::new(to.0, to.1),
+            MovementType::Foot,
+            Team::Blue,
+            occupants,
+            MoveRules::default(),
+        )
+    }
+
+    #[test]
+    fn path_goes_around_impassable_terrain() {
+        let map: Vec<&[TerrainClass]> =
+            vec![&[Grass, Water, Water, Grass], &[Grass, Grass, Grass, Grass]];
+        let path = path(&map, (0, 0), (3, 0), &HashMap::new()).unwrap();
+
+        assert_eq!(path.cost, 5);
+        assert_eq!(path.steps.first(), Some(&GridPos::new(0, 0)));
+        assert_eq!(path.steps.last(), Some(&GridPos::new(3, 0)));
+        assert_eq!(path.steps.len(), 6);
+    }
+
+    #[test]
+    fn path_is_none_when_the_destination_is_cut_off() {
+        let map: Vec<&[TerrainClass]> = vec![&[Grass, Water, Grass]];
+
+        assert_eq!(path(&map, (0, 0), (2, 0), &HashMap::new()), None);
+    }
+
+    #[test]
+    fn path_crosses_allies_but_not_enemies() {
+        let map: Vec<&[TerrainClass]> = vec![&[Grass; 3], &[Grass; 3]];
+        let allies = HashMap::from([(GridPos::new(1, 0), Team::Blue)]);
+        let enemies = HashMap::from([(GridPos::new(1, 0), Team::Red)]);
+
+        assert_eq!(path(&map, (0, 0), (2, 0), &allies).unwrap().cost, 2);
+        assert_eq!(path(&map, (0, 0), (2, 0), &enemies).unwrap().cost, 4);
+        assert_eq!(path(&map, (0, 0), (1, 0), &allies), None);
+    }
+
+    #[test]
+    fn zone_of_control_stops_paths_next_to_enemies() {
+        let map: Vec<&[TerrainClass]> = vec![&[Grass; 4], &[Grass; 4], &[Grass; 4]];
+        let enemies = HashMap::from([(GridPos::new(2, 2), Team::Red)]);
+        let zone_of_control = MoveRules {
+            zone_of_control: true,
+        };
+        let path = |to: (i32, i32), rules| {
+            find_path(
+                &map[..],
+                GridPos::new(0, 1),
+                GridPos::new(to.0, to.1),
+                MovementType::Foot,
+                Team::Blue,
+                &enemies,
+                rules,
+            )
+        };
+
+        // The straight line runs past the enemy.
+        assert_eq!(path((3, 1), MoveRules::default()).unwrap().cost, 3);
+        // With zone of control it has to keep its distance.
+        assert_eq!(path((3, 1), zone_of_control).unwrap().cost, 5);
+        // Stopping next to the enemy is still allowed.
+        assert_eq!(path((2, 1), zone_of_control).unwrap().cost, 2);
+    }
+
+    #[test]
+    fn paths_within_a_range_stay_inside_it() {
+        let map: Vec<&[TerrainClass]> = vec![&[Grass, Water, Grass], &[Grass; 3]];
+        let mover = Mover {
+            start: GridPos::new(0, 0),
+            movement: 3,
+            movement_type: MovementType::Foot,
+            team: Team::Blue,
+        };
+        let range = movement_range(&map[..], &mover, &HashMap::new(), MoveRules::default());
+        let within = WithinRange {
+            map: &map[..],
+            range: &range,
+        };
+        let path = |to| {
+            find_path(
+                &within,
+                mover.start,
+                to,
+                MovementType::Foot,
+                Team::Blue,
+                &HashMap::new(),
+                MoveRules::default(),
+            )
+        };
+
+        assert_eq!(path(GridPos::new(2, 1)).unwrap().cost, 3);
+        // Reachable on an open map, but not with 3 movement points.
+        assert_eq!(path(GridPos::new(2, 0)), None);
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    battlefield::Battlefield,
+    grid::GridPos,
+    unit::{unit_at, Health, Unit},
+};
+
+/// Sent when the pointer moves onto a different tile.
+pub struct TileHovered {
+    pub grid_pos: GridPos,
+    pub unit: Option<Entity>,
+}
+
+/// Sent when a tile is chosen, either by clicking it or by confirming the
+/// grid cursor on it.
+pub struct TileSelected {
+    pub grid_pos: GridPos,
+    pub unit: Option<Entity>,
+}
+
+/// Sent when the player backs out of the current selection.
+pub struct SelectionCancelled;
+
+#[derive(Resource, Default)]
+pub struct HoveredTile(pub Option<GridPos>);
+
+/// Converts the mouse cursor into a world position. Both the cursor and the
+/// camera viewport are in logical pixels, so the window's scale factor
+/// override cancels out.
+fn cursor_world_position(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    camera.viewport_to_world_2d(camera_transform, window.cursor_position()?)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn mouse_picking_system(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    battlefield: Res<Battlefield>,
+    units: Query<(Entity, &GridPos, &Health), With<Unit>>,
+    mut hovered_tile: ResMut<HoveredTile>,
+    mut hovered_events: EventWriter<TileHovered>,
+    mut selected_events: EventWriter<TileSelected>,
+    mut cancelled_events: EventWriter<SelectionCancelled>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+
+    let grid_pos = cursor_world_position(window, camera, camera_transform)
+        .and_then(|world_pos| battlefield.world_to_grid(world_pos));
+
+    if hovered_tile.0 != grid_pos {
+        hovered_tile.0 = grid_pos;
+        if let Some(grid_pos) = grid_pos {
+            hovered_events.send(TileHovered {
+                grid_pos,
+                unit: unit_at(&units, grid_pos),
+            });
+        }
+    }
+
+    if mouse_buttons.just_pressed(MouseButton::Left) {
+        if let Some(grid_pos) = grid_pos {
+            selected_events.send(TileSelected {
+                grid_pos,
+                unit: unit_at(&units, grid_pos),
+            });
+        }
+    }
+
+    if mouse_buttons.just_pressed(MouseButton::Right) {
+        cancelled_events.send(SelectionCancelled);
+    }
+}
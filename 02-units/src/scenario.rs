@@ -0,0 +1,321 @@
+//! Scenario files list the units deployed at the start of a battle. They
+//! are spawned onto whichever battlefield is loaded.
+
+use std::collections::HashSet;
+
+use bevy::{
+    asset::{AssetLoader, Error, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{
+    atlas::UnitAtlasCache,
+    battlefield::Battlefield,
+    class::{ClassDefinition, ClassDefinitions, HairStyle},
+    facing::Facing,
+    grid::GridPos,
+    palette::PaletteSwaps,
+    turn::BattleState,
+    unit::{AiProfile, Level, Team, UnitBundle, UnitClass, UnitSprite},
+    weapon::{ActiveArmory, Armory, Weapon},
+};
+
+pub const DEFAULT_SCENARIO: &str = "Scenarios/green_fields.scenario.ron";
+
+#[derive(Debug, Deserialize)]
+pub struct Deployment {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub class: UnitClass,
+    pub team: Team,
+    #[serde(default = "default_palette")]
+    pub palette: u8,
+    #[serde(default)]
+    pub hair: Option<HairStyle>,
+    #[serde(default = "default_level")]
+    pub level: u32,
+    pub position: GridPos,
+    #[serde(default)]
+    pub facing: Facing,
+    /// Leave out for units the player controls. Every other unit needs one,
+    /// or its phase would never end.
+    #[serde(default)]
+    pub ai: Option<AiProfile>,
+    /// A `*.palette.ron` file to recolour the unit with. Its skin and hair
+    /// still follow `palette`.
+    #[serde(default)]
+    pub recolor: Option<String>,
+    /// The name of a weapon in the armory. Defaults to the first one the
+    /// class can wield.
+    #[serde(default)]
+    pub weapon: Option<String>,
+}
+
+fn default_palette() -> u8 {
+    1
+}
+
+fn default_level() -> u32 {
+    1
+}
+
+impl Deployment {
+    fn describe(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!(
+                "{:?} {:?} at ({}, {})",
+                self.team, self.class, self.position.x, self.position.y
+            ),
+        }
+    }
+
+    /// The weapon the unit is deployed with, if its class can wield it.
+    pub fn weapon<'a>(
+        &self,
+        definition: &ClassDefinition,
+        armory: &'a Armory,
+    ) -> Result<Option<&'a Weapon>, Error> {
+        let Some(name) = &self.weapon else {
+            return Ok(armory.starting_weapon(&definition.weapons));
+        };
+        let weapon = armory.weapon(name).ok_or_else(|| {
+            Error::msg(format!(
+                "{} carries an unknown weapon, {name}",
+                self.describe()
+            ))
+        })?;
+        if !definition.can_wield(weapon.weapon_type) {
+            return Err(Error::msg(format!(
+                "{} cannot wield {name}, {} do not use {:?}s",
+                self.describe(),
+                definition.name,
+                weapon.weapon_type
+            )));
+        }
+
+        Ok(Some(weapon))
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "8a3f61c2-d94e-4b07-9c5a-2e17b08f4d63"]
+pub struct Scenario {
+    pub name: String,
+    pub units: Vec<Deployment>,
+}
+
+impl Scenario {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let scenario: Scenario = ron::de::from_bytes(bytes)?;
+
+        for deployment in &scenario.units {
+            if deployment.level == 0 || deployment.palette == 0 {
+                return Err(Error::msg(format!(
+                    "{} needs a level and palette of at least 1",
+                    deployment.describe()
+                )));
+            }
+            let player_controlled = Some(deployment.team) == BattleState::PlayerPhase.team();
+            if !player_controlled && deployment.ai.is_none() {
+                return Err(Error::msg(format!(
+                    "{} is not on the player's team and needs an ai profile",
+                    deployment.describe()
+                )));
+            }
+        }
+
+        Ok(scenario)
+    }
+
+    /// Checks that every unit has a sprite sheet and a weapon its class can
+    /// wield, and stands alone on a tile it could walk on.
+    pub fn validate(
+        &self,
+        battlefield: &Battlefield,
+        class_definitions: &ClassDefinitions,
+        definitions: &Assets<ClassDefinition>,
+        armory: &Armory,
+    ) -> Result<(), Error> {
+        let mut occupied = HashSet::new();
+
+        for deployment in &self.units {
+            let unit = deployment.describe();
+            let definition = class_definitions
+                .get(deployment.class, definitions)
+                .ok_or_else(|| Error::msg(format!("{unit} has no class definition")))?;
+
+            if definition
+                .sprite_sheet(deployment.team, deployment.palette, deployment.hair)
+                .is_none()
+            {
+                return Err(Error::msg(format!("{unit} has no matching sprite sheet")));
+            }
+
+            let tile = battlefield
+                .tile(deployment.position)
+                .ok_or_else(|| Error::msg(format!("{unit} is outside the map")))?;
+            if !tile.terrain().is_passable(definition.movement_type) {
+                return Err(Error::msg(format!(
+                    "{unit} stands on {}, which it cannot cross",
+                    tile.terrain().name
+                )));
+            }
+
+            if deployment.recolor.is_some() && definition.reference_sheet(deployment.hair).is_none()
+            {
+                return Err(Error::msg(format!(
+                    "{unit} cannot be recoloured, {} has no reference sheet",
+                    definition.name
+                )));
+            }
+
+            deployment.weapon(definition, armory)?;
+
+            if !occupied.insert(deployment.position) {
+                return Err(Error::msg(format!(
+                    "{unit} shares its tile with another unit"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct ScenarioLoader;
+
+impl AssetLoader for ScenarioLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(Scenario::parse(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scenario.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct ActiveScenario(pub Handle<Scenario>);
+
+/// Inserted once the scenario's units are on the battlefield.
+#[derive(Resource)]
+pub struct ScenarioDeployed;
+
+pub fn load_scenario_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ActiveScenario(asset_server.load(DEFAULT_SCENARIO)));
+}
+
+/// Everything needed to turn a class's sheets into unit sprites.
+#[derive(SystemParam)]
+pub struct UnitSprites<'w> {
+    asset_server: Res<'w, AssetServer>,
+    images: Res<'w, Assets<Image>>,
+    palette_swaps: ResMut<'w, PaletteSwaps>,
+    atlas_cache: ResMut<'w, UnitAtlasCache>,
+    texture_atlases: ResMut<'w, Assets<TextureAtlas>>,
+}
+
+impl UnitSprites<'_> {
+    fn load(
+        &mut self,
+        deployment: &Deployment,
+        definition: &ClassDefinition,
+    ) -> Option<UnitSprite> {
+        let sheet =
+            definition.sprite_sheet(deployment.team, deployment.palette, deployment.hair)?;
+        let image = match &deployment.recolor {
+            Some(palette) => self.palette_swaps.get_or_generate(
+                definition.reference_sheet(deployment.hair)?,
+                sheet,
+                palette,
+                &self.asset_server,
+                &self.images,
+            ),
+            None => self.asset_server.load(sheet),
+        };
+
+        Some(UnitSprite::new(
+            definition,
+            &image,
+            &mut self.atlas_cache,
+            &mut self.texture_atlases,
+        ))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_scenario_system(
+    active_scenario: Res<ActiveScenario>,
+    scenarios: Res<Assets<Scenario>>,
+    battlefield: Res<Battlefield>,
+    class_definitions: Res<ClassDefinitions>,
+    definitions: Res<Assets<ClassDefinition>>,
+    active_armory: Res<ActiveArmory>,
+    armories: Res<Assets<Armory>>,
+    mut sprites: UnitSprites,
+    mut commands: Commands,
+) {
+    let (Some(scenario), Some(armory)) = (
+        scenarios.get(&active_scenario.0),
+        armories.get(&active_armory.0),
+    ) else {
+        return;
+    };
+
+    if let Err(error) = scenario.validate(&battlefield, &class_definitions, &definitions, armory) {
+        error!("Could not deploy scenario \"{}\": {error}", scenario.name);
+        commands.remove_resource::<ActiveScenario>();
+        return;
+    }
+
+    for deployment in &scenario.units {
+        let Some(definition) = class_definitions.get(deployment.class, &definitions) else {
+            continue;
+        };
+        let Some(sprite) = sprites.load(deployment, definition) else {
+            continue;
+        };
+
+        let mut bundle = UnitBundle::new(
+            deployment.class,
+            deployment.team,
+            Level(deployment.level),
+            definition.stats_at_level(deployment.level),
+            deployment.position,
+            sprite,
+            &battlefield,
+        );
+        bundle.facing = deployment.facing;
+
+        let mut unit = commands.spawn(bundle);
+        if let Some(name) = &deployment.name {
+            unit.insert(Name::new(name.clone()));
+        }
+        if let Some(ai) = deployment.ai {
+            unit.insert(ai);
+        }
+        if let Ok(Some(weapon)) = deployment.weapon(definition, armory) {
+            unit.insert(weapon.clone());
+        }
+    }
+
+    info!(
+        "Deployed scenario \"{}\" using {} unit atlases",
+        scenario.name,
+        sprites.atlas_cache.len()
+    );
+    commands.insert_resource(ScenarioDeployed);
+}
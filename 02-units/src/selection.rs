@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    battlefield::Battlefield,
+    class::{ClassDefinition, ClassDefinitions},
+    grid::GridPos,
+    movement::{movement_range, MoveRange, MoveRules, Mover},
+    picking::{SelectionCancelled, TileSelected},
+    turn::{BattleState, TurnStatus},
+    unit::{Health, Stats, Team, UnitClass},
+};
+
+/// The unit the player picked, and where it can move.
+#[derive(Resource)]
+pub struct SelectedUnit {
+    pub entity: Entity,
+    pub range: MoveRange,
+}
+
+/// The team of every living unit, keyed by where it stands.
+pub fn occupants<'a>(
+    units: impl IntoIterator<Item = (&'a GridPos, &'a Team, &'a Health)>,
+) -> HashMap<GridPos, Team> {
+    units
+        .into_iter()
+        .filter(|(_, _, health)| !health.is_dead())
+        .map(|(grid_pos, team, _)| (*grid_pos, *team))
+        .collect()
+}
+
+/// Selects a unit of the team whose phase it is, if it hasn't acted yet,
+/// and works out its movement range. A unit that has moved can still attack
+/// but not move again. Selecting the selected unit again ends its turn where
+/// it stands.
+#[allow(clippy::too_many_arguments)]
+pub fn select_unit_system(
+    mut events: EventReader<TileSelected>,
+    selected: Option<Res<SelectedUnit>>,
+    state: Res<State<BattleState>>,
+    battlefield: Res<Battlefield>,
+    rules: Res<MoveRules>,
+    class_definitions: Res<ClassDefinitions>,
+    definitions: Res<Assets<ClassDefinition>>,
+    mut units: Query<(
+        &GridPos,
+        &Team,
+        &UnitClass,
+        &Stats,
+        &mut TurnStatus,
+        &Health,
+    )>,
+    mut commands: Commands,
+) {
+    for event in events.iter() {
+        let Some(entity) = event.unit else {
+            continue;
+        };
+        let Ok((grid_pos, team, class, stats, status, health)) = units.get(entity) else {
+            continue;
+        };
+        if Some(*team) != state.0.team() || status.is_done() || health.is_dead() {
+            continue;
+        }
+        if selected
+            .as_ref()
+            .is_some_and(|selected| selected.entity == entity)
+        {
+            if let Ok((.., mut status, _)) = units.get_mut(entity) {
+                status.has_moved = true;
+                status.has_acted = true;
+            }
+            commands.remove_resource::<SelectedUnit>();
+            continue;
+        }
+        let Some(definition) = class_definitions.get(*class, &definitions) else {
+            continue;
+        };
+
+        let mover = Mover {
+            start: *grid_pos,
+            movement: if status.has_moved { 0 } else { stats.movement },
+            movement_type: definition.movement_type,
+            team: *team,
+        };
+        let others = occupants(
+            units
+                .iter()
+                .filter(|(other_pos, ..)| *other_pos != grid_pos)
+                .map(|(grid_pos, team, .., health)| (grid_pos, team, health)),
+        );
+        let range = movement_range(battlefield.tilemap(), &mover, &others, *rules);
+
+        commands.insert_resource(SelectedUnit { entity, range });
+    }
+}
+
+pub fn cancel_selection_system(
+    mut events: EventReader<SelectionCancelled>,
+    mut commands: Commands,
+) {
+    if events.iter().next().is_some() {
+        commands.remove_resource::<SelectedUnit>();
+    }
+}
+
+/// Drops the selection when a phase ends.
+pub fn clear_selection_system(mut commands: Commands) {
+    commands.remove_resource::<SelectedUnit>();
+}
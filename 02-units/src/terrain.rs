@@ -0,0 +1,192 @@
+//! Gameplay properties of the ground units stand on, kept apart from the
+//! sprite each tile is drawn with.
+//!
+//! `FullTileset.png` only draws dirt, grass and water. Its grass/dirt edge
+//! pieces are flat blends rather than cliffs, so they count as grass. Its
+//! trees, rocks and walls are drawn on transparent backgrounds that a single
+//! tile layer can't show, so there are no forest, hill, fort or wall terrains
+//! until the tilemap can stack them over the ground.
+
+use serde::Deserialize;
+
+use crate::tilemap::TileType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum TerrainClass {
+    Dirt,
+    Grass,
+    Water,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum MovementType {
+    Foot,
+    Mounted,
+    Armored,
+}
+
+impl MovementType {
+    pub const ALL: [MovementType; 3] = [
+        MovementType::Foot,
+        MovementType::Mounted,
+        MovementType::Armored,
+    ];
+}
+
+#[derive(Debug)]
+pub struct Terrain {
+    pub class: TerrainClass,
+    pub name: &'static str,
+    /// Movement points needed to enter the tile, in `MovementType` order.
+    /// `None` means the tile cannot be entered.
+    movement_costs: [Option<u32>; 3],
+    pub defense: i32,
+    pub avoid: i32,
+    /// Percentage of maximum HP a unit standing here is meant to recover at
+    /// the start of its phase. No terrain heals yet, and nothing applies it.
+    pub healing: u32,
+}
+
+impl Terrain {
+    pub fn movement_cost(&self, movement_type: MovementType) -> Option<u32> {
+        self.movement_costs[movement_type as usize]
+    }
+
+    pub fn is_passable(&self, movement_type: MovementType) -> bool {
+        self.movement_cost(movement_type).is_some()
+    }
+
+    pub fn is_impassable(&self) -> bool {
+        MovementType::ALL
+            .into_iter()
+            .all(|movement_type| !self.is_passable(movement_type))
+    }
+}
+
+impl TerrainClass {
+    pub fn terrain(self) -> &'static Terrain {
+        match self {
+            TerrainClass::Dirt => &Terrain {
+                class: TerrainClass::Dirt,
+                name: "Dirt",
+                movement_costs: [Some(1), Some(1), Some(1)],
+                defense: 0,
+                avoid: 0,
+                healing: 0,
+            },
+            TerrainClass::Grass => &Terrain {
+                class: TerrainClass::Grass,
+                name: "Grass",
+                movement_costs: [Some(1), Some(1), Some(1)],
+                defense: 0,
+                avoid: 5,
+                healing: 0,
+            },
+            TerrainClass::Water => &Terrain {
+                class: TerrainClass::Water,
+                name: "Water",
+                movement_costs: [None, None, None],
+                defense: 0,
+                avoid: 0,
+                healing: 0,
+            },
+        }
+    }
+}
+
+impl TileType {
+    pub fn terrain_class(self) -> TerrainClass {
+        match self {
+            TileType::Brown1 | TileType::Brown2 | TileType::Brown3 | TileType::Brown4 => {
+                TerrainClass::Dirt
+            }
+            TileType::Green1
+            | TileType::Green2
+            | TileType::Green3
+            | TileType::Green4
+            | TileType::BrownGreenUpper1
+            | TileType::BrownGreenUpper2
+            | TileType::BrownGreenUpper3
+            | TileType::BrownGreenUpper5
+            | TileType::BrownGreenUpper7
+            | TileType::BrownGreenMiddle1
+            | TileType::BrownGreenMiddle3
+            | TileType::BrownGreenMiddle4
+            | TileType::BrownGreenMiddle6
+            | TileType::BrownGreenLower1
+            | TileType::BrownGreenLower2
+            | TileType::BrownGreenLower3
+            | TileType::BrownGreenLower5
+            | TileType::BrownGreenLower7 => TerrainClass::Grass,
+            TileType::Water1
+            | TileType::Water2
+            | TileType::Water3
+            | TileType::Water4
+            | TileType::Water5
+            | TileType::WaterBankSouthEast
+            | TileType::WaterBankSouth
+            | TileType::WaterBankSouthWest
+            | TileType::WaterBankEast
+            | TileType::WaterBankWest
+            | TileType::WaterBankNorthEast
+            | TileType::WaterBankNorth
+            | TileType::WaterBankNorthWest
+            | TileType::WaterCornerNorthWest
+            | TileType::WaterCornerNorthEast
+            | TileType::WaterCornerSouthWest
+            | TileType::WaterCornerSouthEast
+            | TileType::WaterChannelVertical
+            | TileType::WaterChannelHorizontal => TerrainClass::Water,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terrain_matches_its_class() {
+        for class in [TerrainClass::Dirt, TerrainClass::Grass, TerrainClass::Water] {
+            assert_eq!(class.terrain().class, class);
+        }
+    }
+
+    #[test]
+    fn land_costs_one_point_for_every_movement_type() {
+        for movement_type in MovementType::ALL {
+            assert_eq!(
+                TerrainClass::Dirt.terrain().movement_cost(movement_type),
+                Some(1)
+            );
+            assert_eq!(
+                TerrainClass::Grass.terrain().movement_cost(movement_type),
+                Some(1)
+            );
+            assert_eq!(
+                TerrainClass::Water.terrain().movement_cost(movement_type),
+                None
+            );
+        }
+        assert!(TerrainClass::Water.terrain().is_impassable());
+        assert!(!TerrainClass::Grass.terrain().is_impassable());
+    }
+
+    #[test]
+    fn edge_pieces_are_grass_and_banks_are_water() {
+        assert_eq!(TileType::Brown3.terrain_class(), TerrainClass::Dirt);
+        assert_eq!(
+            TileType::BrownGreenUpper5.terrain_class(),
+            TerrainClass::Grass
+        );
+        assert_eq!(
+            TileType::WaterBankNorth.terrain_class(),
+            TerrainClass::Water
+        );
+        assert!(TileType::ALL.into_iter().all(|tile_type| tile_type
+            .terrain_class()
+            .terrain()
+            .class
+            == tile_type.terrain_class()));
+    }
+}
@@ -0,0 +1,695 @@
+//! Imports maps saved by the Tiled editor, either as JSON (`.tmj`) or XML
+//! (`.tmx`), into a `MapAsset`.
+//!
+//! Tile layers must be painted with the image of the tileset descriptor the
+//! map is imported against, `Tiles/FullTileset.png` by default, and saved with
+//! the CSV layer format. When several tile layers cover the same cell, the top one
+//! wins. Objects become spawn points and map properties become battle rules.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use bevy::{
+    asset::{AssetLoader, Error, LoadContext},
+    prelude::Handle,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{
+    map::{BattleRules, MapAsset, SpawnPoint},
+    tilemap::TileType,
+    tileset::{Tileset, DEFAULT_TILESET},
+};
+
+const GID_FLIP_FLAGS: u32 = 0xE000_0000;
+
+struct RawTileset {
+    first_gid: u32,
+    source: String,
+}
+
+impl RawTileset {
+    /// Whether this tileset paints with `image`: either an embedded tileset
+    /// whose image has the same file name, or an external `.tsx` file named
+    /// after the image.
+    fn is_drawn_from(&self, image: &str) -> bool {
+        let source = Path::new(&self.source);
+        let image = Path::new(image);
+        if source.extension() == Some("tsx".as_ref()) {
+            source.file_stem().is_some() && source.file_stem() == image.file_stem()
+        } else {
+            source.file_name().is_some() && source.file_name() == image.file_name()
+        }
+    }
+}
+
+struct RawObject {
+    name: String,
+    kind: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    is_tile: bool,
+    properties: HashMap<String, String>,
+}
+
+/// The parts of a Tiled map we care about, independent of the file format.
+struct RawMap {
+    num_columns: usize,
+    num_rows: usize,
+    tile_width: u32,
+    tile_height: u32,
+    tilesets: Vec<RawTileset>,
+    layers: Vec<Vec<u32>>,
+    objects: Vec<RawObject>,
+    properties: HashMap<String, String>,
+}
+
+impl RawMap {
+    fn into_map_asset(mut self, default_name: &str, tileset: &Tileset) -> Result<MapAsset, Error> {
+        if self.tile_width != self.tile_height {
+            return Err(Error::msg(format!(
+                "tiles must be square, found {}x{}",
+                self.tile_width, self.tile_height
+            )));
+        }
+
+        let num_tiles = self.num_columns * self.num_rows;
+        if let Some(layer) = self.layers.iter().find(|layer| layer.len() != num_tiles) {
+            return Err(Error::msg(format!(
+                "tile layer has {} tiles, expected {}",
+                layer.len(),
+                num_tiles
+            )));
+        }
+
+        let mut tiles = Vec::with_capacity(self.num_rows);
+        for row in 0..self.num_rows {
+            let mut tile_row = Vec::with_capacity(self.num_columns);
+            for column in 0..self.num_columns {
+                let index = row * self.num_columns + column;
+                let gid = self
+                    .layers
+                    .iter()
+                    .rev()
+                    .map(|layer| layer[index] & !GID_FLIP_FLAGS)
+                    .find(|gid| *gid != 0)
+                    .ok_or_else(|| Error::msg(format!("no tile at column {column}, row {row}")))?;
+                tile_row.push(self.tile_type(gid, tileset)?);
+            }
+            tiles.push(tile_row);
+        }
+
+        let spawn_points = self
+            .objects
+            .iter()
+            .map(|object| self.spawn_point(object))
+            .collect::<Result<_, _>>()?;
+
+        let name = self
+            .properties
+            .remove("name")
+            .unwrap_or_else(|| default_name.to_string());
+        let author = self.properties.remove("author").unwrap_or_default();
+
+        Ok(MapAsset {
+            name,
+            author,
+            tile_size: self.tile_width as f32,
+            tileset_path: DEFAULT_TILESET.to_string(),
+            tileset: Handle::default(),
+            tiles,
+            ground: None,
+            spawn_points,
+            rules: battle_rules(self.properties)?,
+        })
+    }
+
+    fn tile_type(&self, gid: u32, tileset: &Tileset) -> Result<TileType, Error> {
+        let raw_tileset = self
+            .tilesets
+            .iter()
+            .filter(|raw_tileset| raw_tileset.first_gid <= gid)
+            .max_by_key(|raw_tileset| raw_tileset.first_gid)
+            .ok_or_else(|| Error::msg(format!("tile {gid} does not belong to any tileset")))?;
+
+        if !raw_tileset.is_drawn_from(&tileset.image) {
+            return Err(Error::msg(format!(
+                "tileset \"{}\" is not supported, paint the map with {}",
+                raw_tileset.source, tileset.image
+            )));
+        }
+
+        let index = (gid - raw_tileset.first_gid) as usize;
+        tileset
+            .tile_type_at(index)
+            .ok_or_else(|| Error::msg(format!("tile {index} of {} has no TileType", tileset.image)))
+    }
+
+    fn spawn_point(&self, object: &RawObject) -> Result<SpawnPoint, Error> {
+        // Tile objects are anchored at their bottom-left corner, everything
+        // else at its top-left corner.
+        let center_x = object.x + object.width / 2.0;
+        let center_y = if object.is_tile {
+            object.y - object.height / 2.0
+        } else {
+            object.y + object.height / 2.0
+        };
+
+        let column = (center_x / self.tile_width as f32).floor();
+        let row_from_top = (center_y / self.tile_height as f32).floor();
+        if column < 0.0
+            || row_from_top < 0.0
+            || column as usize >= self.num_columns
+            || row_from_top as usize >= self.num_rows
+        {
+            return Err(Error::msg(format!(
+                "object \"{}\" is outside the map",
+                object.name
+            )));
+        }
+
+        Ok(SpawnPoint {
+            name: object.name.clone(),
+            kind: object.kind.clone(),
+            column: column as usize,
+            row: self.num_rows - 1 - row_from_top as usize,
+            properties: object.properties.clone(),
+        })
+    }
+}
+
+fn battle_rules(mut properties: HashMap<String, String>) -> Result<BattleRules, Error> {
+    let turn_limit = properties
+        .remove("turn_limit")
+        .map(|turn_limit| {
+            turn_limit
+                .parse()
+                .map_err(|_| Error::msg(format!("invalid turn_limit \"{turn_limit}\"")))
+        })
+        .transpose()?;
+
+    Ok(BattleRules {
+        objective: properties.remove("objective"),
+        turn_limit,
+        properties,
+    })
+}
+
+fn parse_csv(csv: &str) -> Result<Vec<u32>, Error> {
+    csv.split(',')
+        .map(str::trim)
+        .filter(|gid| !gid.is_empty())
+        .map(|gid| {
+            gid.parse()
+                .map_err(|_| Error::msg(format!("invalid tile \"{gid}\" in layer data")))
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct TmjMap {
+    width: usize,
+    height: usize,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    layers: Vec<TmjLayer>,
+    tilesets: Vec<TmjTileset>,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TmjLayer {
+    TileLayer {
+        #[serde(default)]
+        encoding: Option<String>,
+        data: serde_json::Value,
+    },
+    ObjectGroup {
+        objects: Vec<TmjObject>,
+    },
+    Group {
+        layers: Vec<TmjLayer>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct TmjTileset {
+    firstgid: u32,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    image: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TmjObject {
+    #[serde(default)]
+    name: String,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+struct TmjProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+fn tmj_properties(properties: Vec<TmjProperty>) -> HashMap<String, String> {
+    properties
+        .into_iter()
+        .map(|property| {
+            let value = match property.value {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+            (property.name, value)
+        })
+        .collect()
+}
+
+fn flatten_tmj_layers(
+    layers: Vec<TmjLayer>,
+    tile_layers: &mut Vec<Vec<u32>>,
+    objects: &mut Vec<RawObject>,
+) -> Result<(), Error> {
+    for layer in layers {
+        match layer {
+            TmjLayer::TileLayer { encoding, data } => {
+                let gids =
+                    match (encoding.as_deref(), data) {
+                        (None | Some("csv"), data @ serde_json::Value::Array(_)) => {
+                            serde_json::from_value(data)?
+                        }
+                        (Some("csv"), serde_json::Value::String(csv)) => parse_csv(&csv)?,
+                        _ => return Err(Error::msg(
+                            "only CSV tile layers are supported, change the layer format in Tiled",
+                        )),
+                    };
+                tile_layers.push(gids);
+            }
+            TmjLayer::ObjectGroup {
+                objects: layer_objects,
+            } => {
+                objects.extend(layer_objects.into_iter().map(|object| RawObject {
+                    name: object.name,
+                    kind: if object.class.is_empty() {
+                        object.kind
+                    } else {
+                        object.class
+                    },
+                    x: object.x,
+                    y: object.y,
+                    width: object.width,
+                    height: object.height,
+                    is_tile: object.gid.is_some(),
+                    properties: tmj_properties(object.properties),
+                }));
+            }
+            TmjLayer::Group { layers } => flatten_tmj_layers(layers, tile_layers, objects)?,
+            TmjLayer::Other => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a Tiled map saved as JSON.
+pub fn parse_tmj(bytes: &[u8], default_name: &str, tileset: &Tileset) -> Result<MapAsset, Error> {
+    let map: TmjMap = serde_json::from_slice(bytes)?;
+    if map.infinite {
+        return Err(Error::msg("infinite maps are not supported"));
+    }
+
+    let mut layers = Vec::new();
+    let mut objects = Vec::new();
+    flatten_tmj_layers(map.layers, &mut layers, &mut objects)?;
+
+    RawMap {
+        num_columns: map.width,
+        num_rows: map.height,
+        tile_width: map.tilewidth,
+        tile_height: map.tileheight,
+        tilesets: map
+            .tilesets
+            .into_iter()
+            .map(|tileset| RawTileset {
+                first_gid: tileset.firstgid,
+                source: tileset.source.or(tileset.image).unwrap_or_default(),
+            })
+            .collect(),
+        layers,
+        objects,
+        properties: tmj_properties(map.properties),
+    }
+    .into_map_asset(default_name, tileset)
+}
+
+fn xml_attribute<T: std::str::FromStr>(node: roxmltree::Node, name: &str) -> Result<T, Error> {
+    let value = node.attribute(name).ok_or_else(|| {
+        Error::msg(format!(
+            "<{}> is missing the \"{name}\" attribute",
+            node.tag_name().name()
+        ))
+    })?;
+
+    value.parse().map_err(|_| {
+        Error::msg(format!(
+            "<{}> has an invalid \"{name}\" attribute \"{value}\"",
+            node.tag_name().name()
+        ))
+    })
+}
+
+fn xml_child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn tmx_properties(node: roxmltree::Node) -> HashMap<String, String> {
+    let Some(properties) = xml_child(node, "properties") else {
+        return HashMap::new();
+    };
+
+    properties
+        .children()
+        .filter(|property| property.has_tag_name("property"))
+        .filter_map(|property| {
+            let value = property
+                .attribute("value")
+                .or_else(|| property.text())
+                .unwrap_or_default();
+            Some((property.attribute("name")?.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn tmx_layer(layer: roxmltree::Node) -> Result<Vec<u32>, Error> {
+    let data = xml_child(layer, "data").ok_or_else(|| Error::msg("tile layer has no data"))?;
+
+    match data.attribute("encoding") {
+        Some("csv") => parse_csv(data.text().unwrap_or_default()),
+        None => data
+            .children()
+            .filter(|tile| tile.has_tag_name("tile"))
+            .map(|tile| Ok(tile.attribute("gid").unwrap_or("0").parse()?))
+            .collect(),
+        Some(_) => Err(Error::msg(
+            "only CSV tile layers are supported, change the layer format in Tiled",
+        )),
+    }
+}
+
+fn tmx_object(object: roxmltree::Node) -> Result<RawObject, Error> {
+    Ok(RawObject {
+        name: object.attribute("name").unwrap_or_default().to_string(),
+        kind: object
+            .attribute("class")
+            .or_else(|| object.attribute("type"))
+            .unwrap_or_default()
+            .to_string(),
+        x: xml_attribute(object, "x")?,
+        y: xml_attribute(object, "y")?,
+        width: xml_attribute(object, "width").unwrap_or_default(),
+        height: xml_attribute(object, "height").unwrap_or_default(),
+        is_tile: object.attribute("gid").is_some(),
+        properties: tmx_properties(object),
+    })
+}
+
+/// Collects the tile layers and objects of the map and its groups. Objects
+/// inside tilesets are collision shapes, not spawn points, so they're left
+/// out.
+fn flatten_tmx_layers(
+    node: roxmltree::Node,
+    tile_layers: &mut Vec<Vec<u32>>,
+    objects: &mut Vec<RawObject>,
+) -> Result<(), Error> {
+    for child in node.children() {
+        match child.tag_name().name() {
+            "layer" => tile_layers.push(tmx_layer(child)?),
+            "objectgroup" => {
+                for object in child
+                    .children()
+                    .filter(|object| object.has_tag_name("object"))
+                {
+                    objects.push(tmx_object(object)?);
+                }
+            }
+            "group" => flatten_tmx_layers(child, tile_layers, objects)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a Tiled map saved as XML.
+pub fn parse_tmx(bytes: &[u8], default_name: &str, tileset: &Tileset) -> Result<MapAsset, Error> {
+    let text = std::str::from_utf8(bytes)?;
+    let document = roxmltree::Document::parse(text)?;
+    let map = document.root_element();
+    if !map.has_tag_name("map") {
+        return Err(Error::msg("not a Tiled map"));
+    }
+    if map.attribute("infinite") == Some("1") {
+        return Err(Error::msg("infinite maps are not supported"));
+    }
+
+    let tilesets = map
+        .children()
+        .filter(|child| child.has_tag_name("tileset"))
+        .map(|tileset| {
+            let source = tileset
+                .attribute("source")
+                .or_else(|| xml_child(tileset, "image")?.attribute("source"))
+                .unwrap_or_default();
+            Ok(RawTileset {
+                first_gid: xml_attribute(tileset, "firstgid")?,
+                source: source.to_string(),
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    let mut layers = Vec::new();
+    let mut objects = Vec::new();
+    flatten_tmx_layers(map, &mut layers, &mut objects)?;
+
+    RawMap {
+        num_columns: xml_attribute(map, "width")?,
+        num_rows: xml_attribute(map, "height")?,
+        tile_width: xml_attribute(map, "tilewidth")?,
+        tile_height: xml_attribute(map, "tileheight")?,
+        tilesets,
+        layers,
+        objects,
+        properties: tmx_properties(map),
+    }
+    .into_map_asset(default_name, tileset)
+}
+
+/// Reads a `.tmj` or `.tmx` map straight from disk, without an `AssetServer`.
+pub fn load_tiled_map(path: impl AsRef<Path>, tileset: &Tileset) -> Result<MapAsset, Error> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+    let default_name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+
+    let map = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("tmj") | Some("json") => parse_tmj(&bytes, default_name, tileset)?,
+        Some("tmx") => parse_tmx(&bytes, default_name, tileset)?,
+        _ => return Err(Error::msg(format!("{} is not a Tiled map", path.display()))),
+    };
+    map.validate()?;
+
+    Ok(map)
+}
+
+#[derive(Default)]
+pub struct TiledMapLoader;
+
+impl AssetLoader for TiledMapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let tileset = Tileset::parse(&load_context.read_asset_bytes(DEFAULT_TILESET).await?)?;
+            let path = load_context.path();
+            let default_name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+
+            let map = if path.extension().and_then(|extension| extension.to_str()) == Some("tmx") {
+                parse_tmx(bytes, default_name, &tileset)?
+            } else {
+                parse_tmj(bytes, default_name, &tileset)?
+            };
+            map.validate()?;
+            map.set_default_asset(load_context);
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmj", "tmx"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::TileType::{Brown1, Green1};
+
+    fn tileset() -> Tileset {
+        Tileset::parse(
+            br#"(
+                image: "Tiles/FullTileset.png",
+                tile_size: 16.0,
+                columns: 2,
+                rows: 1,
+                tiles: {
+                    Brown1: (row: 0, column: 0),
+                    Green1: (row: 0, column: 1),
+                },
+            )"#,
+        )
+        .unwrap()
+    }
+
+    fn tmj(properties: &str) -> String {
+        format!(
+            r#"{{
+                "width": 2, "height": 2, "tilewidth": 16, "tileheight": 16,
+                "tilesets": [{{ "firstgid": 1, "source": "FullTileset.tsx" }}],
+                "layers": [
+                    {{ "type": "tilelayer", "data": [1, 2147483650, 1073741826, 1] }},
+                    {{ "type": "objectgroup", "objects": [
+                        {{ "name": "Leader", "type": "ally", "x": 16, "y": 0,
+                           "width": 16, "height": 16 }},
+                        {{ "name": "Chest", "gid": 1, "x": 0, "y": 32,
+                           "width": 16, "height": 16 }}
+                    ] }}
+                ],
+                "properties": [{properties}]
+            }}"#
+        )
+    }
+
+    #[test]
+    fn tmj_masks_flip_flags_and_counts_spawn_rows_from_the_bottom() {
+        let map = parse_tmj(
+            tmj(r#"{ "name": "turn_limit", "type": "int", "value": 12 }"#).as_bytes(),
+            "test",
+            &tileset(),
+        )
+        .unwrap();
+
+        assert_eq!(map.name, "test");
+        assert_eq!(map.tiles, vec![vec![Brown1, Green1], vec![Green1, Brown1]]);
+        assert_eq!(map.rules.turn_limit, Some(12));
+
+        let leader = &map.spawn_points[0];
+        assert_eq!(
+            (leader.kind.as_str(), leader.column, leader.row),
+            ("ally", 1, 1)
+        );
+        // Tile objects hang up from their bottom-left corner.
+        let chest = &map.spawn_points[1];
+        assert_eq!((chest.column, chest.row), (0, 0));
+    }
+
+    #[test]
+    fn tilesets_must_be_named_after_the_image() {
+        let drawn_from = |source: &str| {
+            RawTileset {
+                first_gid: 1,
+                source: source.to_string(),
+            }
+            .is_drawn_from("Tiles/FullTileset.png")
+        };
+
+        assert!(drawn_from("FullTileset.tsx"));
+        assert!(drawn_from("../tilesets/FullTileset.tsx"));
+        assert!(drawn_from("FullTileset.png"));
+        assert!(drawn_from("../assets/Tiles/FullTileset.png"));
+        assert!(!drawn_from("OldFullTileset.png"));
+        assert!(!drawn_from("FullTileset.png.bak"));
+        assert!(!drawn_from("FullTilesetV2.tsx"));
+        assert!(!drawn_from("FullTileset.tsx.png"));
+        assert!(!drawn_from(""));
+    }
+
+    #[test]
+    fn invalid_turn_limit_is_rejected() {
+        let bytes = tmj(r#"{ "name": "turn_limit", "type": "string", "value": "soon" }"#);
+
+        assert!(parse_tmj(bytes.as_bytes(), "test", &tileset()).is_err());
+    }
+
+    #[test]
+    fn tmx_ignores_collision_shapes_inside_tilesets() {
+        let tmx = br#"<?xml version="1.0" encoding="UTF-8"?>
+            <map width="2" height="2" tilewidth="16" tileheight="16" infinite="0">
+                <properties>
+                    <property name="name" value="Crossing"/>
+                    <property name="objective" value="Rout the enemy"/>
+                </properties>
+                <tileset firstgid="1" name="FullTileset" tilewidth="16" tileheight="16">
+                    <image source="FullTileset.png" width="32" height="16"/>
+                    <tile id="0">
+                        <objectgroup>
+                            <object id="1" x="0" y="0" width="16" height="16"/>
+                        </objectgroup>
+                    </tile>
+                </tileset>
+                <layer id="1" width="2" height="2">
+                    <data encoding="csv">2,1,3221225473,2</data>
+                </layer>
+                <group id="2">
+                    <objectgroup id="3">
+                        <object id="2" name="Boss" type="enemy" x="0" y="16" width="16" height="16">
+                            <properties><property name="class" value="AxeKnight"/></properties>
+                        </object>
+                    </objectgroup>
+                </group>
+            </map>"#;
+        let map = parse_tmx(tmx, "test", &tileset()).unwrap();
+
+        assert_eq!(map.name, "Crossing");
+        assert_eq!(map.rules.objective.as_deref(), Some("Rout the enemy"));
+        assert_eq!(map.tiles, vec![vec![Green1, Brown1], vec![Brown1, Green1]]);
+        assert_eq!(map.spawn_points.len(), 1);
+
+        let boss = &map.spawn_points[0];
+        assert_eq!((boss.name.as_str(), boss.column, boss.row), ("Boss", 0, 0));
+        assert_eq!(boss.properties["class"], "AxeKnight");
+    }
+}
@@ -0,0 +1,328 @@
+use serde::Deserialize;
+
+use crate::terrain::Terrain;
+
+pub struct Tilemap {
+    data: Vec<Tile>,
+    num_columns: usize,
+    num_rows: usize,
+}
+
+impl Tilemap {
+    /// Builds a tilemap from tiles stored row by row, bottom row first.
+    pub fn new(data: Vec<Tile>, num_columns: usize, num_rows: usize) -> Option<Self> {
+        if num_columns == 0 || num_rows == 0 || data.len() != num_columns * num_rows {
+            return None;
+        }
+
+        Some(Self {
+            data,
+            num_columns,
+            num_rows,
+        })
+    }
+
+    /// Builds a tilemap from map file rows, which are listed top row first.
+    /// The tilemap stores the bottom row first, matching the world's Y axis.
+    pub fn from_rows(rows: &[Vec<TileType>]) -> Option<Self> {
+        let num_rows = rows.len();
+        let num_columns = rows.first()?.len();
+        if rows.iter().any(|row| row.len() != num_columns) {
+            return None;
+        }
+
+        let data = rows
+            .iter()
+            .rev()
+            .flatten()
+            .map(|tile_type| Tile::from_type(*tile_type))
+            .collect();
+
+        Self::new(data, num_columns, num_rows)
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.num_columns
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.num_columns && y < self.num_rows
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&Tile> {
+        if !self.contains(x, y) {
+            return None;
+        }
+
+        self.data.get(y * self.num_columns + x)
+    }
+
+    pub fn terrain(&self, x: usize, y: usize) -> Option<&'static Terrain> {
+        self.get(x, y).map(Tile::terrain)
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Tile> {
+        if !self.contains(x, y) {
+            return None;
+        }
+
+        self.data.get_mut(y * self.num_columns + x)
+    }
+
+    /// Iterates over every tile as `(x, y, tile)`, bottom row first.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &Tile)> {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(index, tile)| (index % self.num_columns, index / self.num_columns, tile))
+    }
+
+    /// Iterates over the orthogonal neighbours of a tile that are inside the map.
+    pub fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize, &Tile)> {
+        let candidates = [
+            (x.checked_sub(1), Some(y)),
+            (x.checked_add(1), Some(y)),
+            (Some(x), y.checked_sub(1)),
+            (Some(x), y.checked_add(1)),
+        ];
+
+        candidates.into_iter().filter_map(move |(x, y)| {
+            let (x, y) = (x?, y?);
+            self.get(x, y).map(|tile| (x, y, tile))
+        })
+    }
+
+    pub fn row(&self, y: usize) -> Option<&[Tile]> {
+        self.rows().nth(y)
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Tile]> {
+        self.data.chunks(self.num_columns)
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &Tile> {
+        let num_rows = if x < self.num_columns {
+            self.num_rows
+        } else {
+            0
+        };
+
+        self.data
+            .iter()
+            .skip(x)
+            .step_by(self.num_columns)
+            .take(num_rows)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &Tile>> {
+        (0..self.num_columns).map(move |x| self.column(x))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum TileType {
+    Brown1,
+    Brown2,
+    Brown3,
+    Brown4,
+    Green1,
+    Green2,
+    Green3,
+    Green4,
+    BrownGreenUpper1,
+    BrownGreenUpper2,
+    BrownGreenUpper3,
+    BrownGreenUpper5,
+    BrownGreenUpper7,
+    BrownGreenMiddle1,
+    BrownGreenMiddle3,
+    BrownGreenMiddle4,
+    BrownGreenMiddle6,
+    BrownGreenLower1,
+    BrownGreenLower2,
+    BrownGreenLower3,
+    BrownGreenLower5,
+    BrownGreenLower7,
+    // Water tiles are named after where they meet land: banks border land on
+    // one side or corner, corners on two adjacent sides and channels on two
+    // opposite sides.
+    Water1,
+    Water2,
+    Water3,
+    Water4,
+    Water5,
+    WaterBankSouthEast,
+    WaterBankSouth,
+    WaterBankSouthWest,
+    WaterBankEast,
+    WaterBankWest,
+    WaterBankNorthEast,
+    WaterBankNorth,
+    WaterBankNorthWest,
+    WaterCornerNorthWest,
+    WaterCornerNorthEast,
+    WaterCornerSouthWest,
+    WaterCornerSouthEast,
+    WaterChannelVertical,
+    WaterChannelHorizontal,
+}
+
+impl TileType {
+    pub const ALL: [TileType; 41] = [
+        TileType::Brown1,
+        TileType::Brown2,
+        TileType::Brown3,
+        TileType::Brown4,
+        TileType::Green1,
+        TileType::Green2,
+        TileType::Green3,
+        TileType::Green4,
+        TileType::BrownGreenUpper1,
+        TileType::BrownGreenUpper2,
+        TileType::BrownGreenUpper3,
+        TileType::BrownGreenUpper5,
+        TileType::BrownGreenUpper7,
+        TileType::BrownGreenMiddle1,
+        TileType::BrownGreenMiddle3,
+        TileType::BrownGreenMiddle4,
+        TileType::BrownGreenMiddle6,
+        TileType::BrownGreenLower1,
+        TileType::BrownGreenLower2,
+        TileType::BrownGreenLower3,
+        TileType::BrownGreenLower5,
+        TileType::BrownGreenLower7,
+        TileType::Water1,
+        TileType::Water2,
+        TileType::Water3,
+        TileType::Water4,
+        TileType::Water5,
+        TileType::WaterBankSouthEast,
+        TileType::WaterBankSouth,
+        TileType::WaterBankSouthWest,
+        TileType::WaterBankEast,
+        TileType::WaterBankWest,
+        TileType::WaterBankNorthEast,
+        TileType::WaterBankNorth,
+        TileType::WaterBankNorthWest,
+        TileType::WaterCornerNorthWest,
+        TileType::WaterCornerNorthEast,
+        TileType::WaterCornerSouthWest,
+        TileType::WaterCornerSouthEast,
+        TileType::WaterChannelVertical,
+        TileType::WaterChannelHorizontal,
+    ];
+}
+
+#[derive(Clone, Copy)]
+pub struct Tile {
+    pub tile_type: TileType,
+}
+
+impl Tile {
+    pub fn from_type(tile_type: TileType) -> Tile {
+        Tile { tile_type }
+    }
+
+    pub fn terrain(&self) -> &'static Terrain {
+        self.tile_type.terrain_class().terrain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::TileType::{Brown1, Green1, Water1};
+
+    /// A 40×30 map of grass, with dirt along its top row and water down its
+    /// last column.
+    fn map() -> Tilemap {
+        let mut rows = vec![vec![Green1; 40]; 30];
+        rows[0].fill(Brown1);
+        for row in &mut rows {
+            row[39] = Water1;
+        }
+
+        Tilemap::from_rows(&rows).unwrap()
+    }
+
+    fn tile_types<'a>(tiles: impl IntoIterator<Item = &'a Tile>) -> Vec<TileType> {
+        tiles.into_iter().map(|tile| tile.tile_type).collect()
+    }
+
+    #[test]
+    fn from_rows_stores_the_bottom_row_first() {
+        let map = map();
+
+        assert_eq!((map.num_columns(), map.num_rows()), (40, 30));
+        assert_eq!(map.get(0, 0).unwrap().tile_type, Green1);
+        assert_eq!(map.get(0, 29).unwrap().tile_type, Brown1);
+        assert_eq!(map.get(39, 0).unwrap().tile_type, Water1);
+    }
+
+    #[test]
+    fn from_rows_rejects_ragged_and_empty_rows() {
+        assert!(Tilemap::from_rows(&[vec![Green1; 3], vec![Green1; 2]]).is_none());
+        assert!(Tilemap::from_rows(&[]).is_none());
+        assert!(Tilemap::from_rows(&[vec![]]).is_none());
+        assert!(Tilemap::new(vec![Tile::from_type(Green1); 5], 2, 3).is_none());
+    }
+
+    #[test]
+    fn get_is_bounds_checked() {
+        let mut map = map();
+
+        assert!(map.get(39, 29).is_some());
+        assert!(map.get(40, 0).is_none());
+        assert!(map.get(0, 30).is_none());
+        assert!(map.get(30, 39).is_none());
+        assert!(map.get_mut(40, 29).is_none());
+
+        map.get_mut(5, 5).unwrap().tile_type = Water1;
+        assert_eq!(map.terrain(5, 5).unwrap().name, "Water");
+    }
+
+    #[test]
+    fn neighbours_stay_inside_the_map() {
+        let map = map();
+        let neighbours = |x, y| {
+            let mut positions: Vec<_> = map.neighbours(x, y).map(|(x, y, _)| (x, y)).collect();
+            positions.sort();
+            positions
+        };
+
+        assert_eq!(neighbours(0, 0), [(0, 1), (1, 0)]);
+        assert_eq!(neighbours(39, 29), [(38, 29), (39, 28)]);
+        assert_eq!(neighbours(20, 0), [(19, 0), (20, 1), (21, 0)]);
+        assert_eq!(neighbours(20, 15).len(), 4);
+    }
+
+    #[test]
+    fn rows_and_columns_walk_the_grid() {
+        let map = map();
+
+        assert_eq!(map.rows().count(), 30);
+        assert_eq!(map.columns().count(), 40);
+        assert_eq!(
+            tile_types(map.row(29).unwrap()),
+            vec![Brown1; 39]
+                .into_iter()
+                .chain([Water1])
+                .collect::<Vec<_>>()
+        );
+        assert!(map.row(30).is_none());
+
+        let last_column = tile_types(map.column(39));
+        assert_eq!(last_column, vec![Water1; 30]);
+        let first_column = tile_types(map.column(0));
+        assert_eq!(first_column.len(), 30);
+        assert_eq!(first_column.last(), Some(&Brown1));
+        assert_eq!(map.column(40).count(), 0);
+
+        let (x, y, _) = map.iter().nth(41).unwrap();
+        assert_eq!((x, y), (1, 1));
+    }
+}
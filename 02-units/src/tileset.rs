@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, Error, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::tilemap::TileType;
+
+pub const DEFAULT_TILESET: &str = "Tiles/FullTileset.tileset.ron";
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct TilePosition {
+    pub row: usize,
+    pub column: usize,
+}
+
+/// Layout of an animated water sheet. Each frame is a block of
+/// `rows_per_frame` rows, laid out like the still water tiles that start at
+/// `first_row` of the tileset image.
+#[derive(Debug, Deserialize)]
+pub struct WaterSheet {
+    pub image: String,
+    pub first_row: usize,
+    pub columns: usize,
+    pub rows_per_frame: usize,
+    pub frames: usize,
+    pub frame_seconds: f32,
+}
+
+/// Names the regions of a tileset image, so `TileType`s don't depend on
+/// where a particular sheet happens to draw them.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "9b7e4c21-5d3a-4e8f-a6b2-0c1d7f93e845"]
+pub struct Tileset {
+    pub image: String,
+    pub tile_size: f32,
+    pub columns: usize,
+    pub rows: usize,
+    tiles: HashMap<TileType, TilePosition>,
+    #[serde(default)]
+    pub water: Option<WaterSheet>,
+    #[serde(skip)]
+    pub image_handle: Handle<Image>,
+    #[serde(skip)]
+    pub water_image_handle: Option<Handle<Image>>,
+}
+
+impl Tileset {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let tileset: Tileset = ron::de::from_bytes(bytes)?;
+        tileset.validate()?;
+
+        Ok(tileset)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.tile_size <= 0.0 || self.columns == 0 || self.rows == 0 {
+            return Err(Error::msg(format!(
+                "tileset {} has an empty grid",
+                self.image
+            )));
+        }
+
+        if let Some((tile_type, position)) = self
+            .tiles
+            .iter()
+            .find(|(_, position)| position.column >= self.columns || position.row >= self.rows)
+        {
+            return Err(Error::msg(format!(
+                "{tile_type:?} at row {}, column {} is outside tileset {}",
+                position.row, position.column, self.image
+            )));
+        }
+
+        if let Some(water) = &self.water {
+            if water.columns > self.columns || water.first_row + water.rows_per_frame > self.rows {
+                return Err(Error::msg(format!(
+                    "water tiles of tileset {} don't fit in the tileset",
+                    self.image
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the descriptor against the real size of the loaded tileset image.
+    pub fn validate_image(&self, image: &Image) -> Result<(), Error> {
+        validate_image_size(&self.image, image, self.columns, self.rows, self.tile_size)
+    }
+
+    /// Checks the water layout against the real size of the loaded water image.
+    pub fn validate_water_image(&self, water_image: &Image) -> Result<(), Error> {
+        let Some(water) = &self.water else {
+            return Ok(());
+        };
+
+        validate_image_size(
+            &water.image,
+            water_image,
+            water.columns,
+            water.rows_per_frame * water.frames,
+            self.tile_size,
+        )
+    }
+
+    pub fn contains(&self, tile_type: TileType) -> bool {
+        self.tiles.contains_key(&tile_type)
+    }
+
+    pub fn position(&self, tile_type: TileType) -> Option<TilePosition> {
+        self.tiles.get(&tile_type).copied()
+    }
+
+    /// Atlas index of a tile type, counting left to right, top to bottom.
+    pub fn index(&self, tile_type: TileType) -> Option<usize> {
+        let position = self.position(tile_type)?;
+
+        Some(position.row * self.columns + position.column)
+    }
+
+    /// Looks up the tile type drawn at `index` in the tileset atlas.
+    pub fn tile_type_at(&self, index: usize) -> Option<TileType> {
+        TileType::ALL
+            .into_iter()
+            .find(|tile_type| self.index(*tile_type) == Some(index))
+    }
+
+    pub fn texture_atlas(&self) -> TextureAtlas {
+        TextureAtlas::from_grid(
+            self.image_handle.clone(),
+            Vec2::new(self.tile_size, self.tile_size),
+            self.columns,
+            self.rows,
+            None,
+            None,
+        )
+    }
+}
+
+fn validate_image_size(
+    path: &str,
+    image: &Image,
+    columns: usize,
+    rows: usize,
+    tile_size: f32,
+) -> Result<(), Error> {
+    let expected = Vec2::new(columns as f32, rows as f32) * tile_size;
+    let size = image.size();
+    if size != expected {
+        return Err(Error::msg(format!(
+            "{path} is {}x{} pixels but its tileset expects {}x{}",
+            size.x, size.y, expected.x, expected.y
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct TilesetLoader;
+
+impl AssetLoader for TilesetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let mut tileset = Tileset::parse(bytes)?;
+
+            let image_path = AssetPath::from(tileset.image.as_str()).to_owned();
+            tileset.image_handle = load_context.get_handle(image_path.clone());
+            let mut dependencies = vec![image_path];
+
+            if let Some(water) = &tileset.water {
+                let water_path = AssetPath::from(water.image.as_str()).to_owned();
+                tileset.water_image_handle = Some(load_context.get_handle(water_path.clone()));
+                dependencies.push(water_path);
+            }
+
+            load_context
+                .set_default_asset(LoadedAsset::new(tileset).with_dependencies(dependencies));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tileset.ron"]
+    }
+}
@@ -0,0 +1,289 @@
+//! Battle flow: deployment, then each team's phase in turn until one side
+//! is wiped out.
+
+use bevy::prelude::*;
+
+use crate::unit::{AiProfile, Health, Team, Unit};
+
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BattleState {
+    /// Waiting for the battlefield and scenario to be spawned.
+    #[default]
+    Deployment,
+    PlayerPhase,
+    EnemyPhase,
+    OtherFactionPhase,
+    BattleEnd,
+}
+
+impl BattleState {
+    /// The team that moves during a phase.
+    pub fn team(self) -> Option<Team> {
+        match self {
+            BattleState::PlayerPhase => Some(Team::Blue),
+            BattleState::EnemyPhase => Some(Team::Red),
+            BattleState::OtherFactionPhase => Some(Team::Green),
+            BattleState::Deployment | BattleState::BattleEnd => None,
+        }
+    }
+
+    /// The phase after this one. The player phase follows the last phase of
+    /// a turn.
+    pub fn next_phase(self) -> BattleState {
+        match self {
+            BattleState::Deployment => BattleState::PlayerPhase,
+            BattleState::PlayerPhase => BattleState::EnemyPhase,
+            BattleState::EnemyPhase => BattleState::OtherFactionPhase,
+            BattleState::OtherFactionPhase => BattleState::PlayerPhase,
+            BattleState::BattleEnd => BattleState::BattleEnd,
+        }
+    }
+}
+
+/// Counts turns from 1. A turn is one phase of every team.
+#[derive(Resource, Default)]
+pub struct TurnCounter {
+    pub turn: u32,
+}
+
+/// What a unit has done during its team's phase.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TurnStatus {
+    pub has_moved: bool,
+    pub has_acted: bool,
+}
+
+impl TurnStatus {
+    /// Acting ends a unit's phase, whether or not it moved first.
+    pub fn is_done(&self) -> bool {
+        self.has_acted
+    }
+}
+
+/// Sent when a team's phase begins, after its units are ready to move.
+pub struct PhaseStarted {
+    pub phase: BattleState,
+    pub turn: u32,
+}
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BattleOutcome {
+    pub winner: Team,
+}
+
+pub fn is_phase(state: Res<State<BattleState>>) -> bool {
+    state.0.team().is_some()
+}
+
+pub fn finish_deployment_system(mut next_state: ResMut<NextState<BattleState>>) {
+    next_state.set(BattleState::PlayerPhase);
+}
+
+/// Readies the units of the team whose phase is starting.
+pub fn start_phase_system(
+    state: Res<State<BattleState>>,
+    mut turn_counter: ResMut<TurnCounter>,
+    mut units: Query<(&Team, &mut TurnStatus), With<Unit>>,
+    mut phase_started: EventWriter<PhaseStarted>,
+) {
+    let phase = state.0;
+    if phase == BattleState::PlayerPhase {
+        turn_counter.turn += 1;
+    }
+
+    for (team, mut status) in &mut units {
+        if Some(*team) == phase.team() {
+            *status = TurnStatus::default();
+        }
+    }
+
+    info!("Turn {}: {phase:?}", turn_counter.turn);
+    phase_started.send(PhaseStarted {
+        phase,
+        turn: turn_counter.turn,
+    });
+}
+
+/// Moves to the next phase once every living unit of the current team is
+/// done. Phases of teams without units pass straight away.
+pub fn end_phase_when_done_system(
+    state: Res<State<BattleState>>,
+    units: Query<(&Team, &TurnStatus, &Health), With<Unit>>,
+    mut next_state: ResMut<NextState<BattleState>>,
+) {
+    let team = state.0.team();
+    let all_done = units
+        .iter()
+        .filter(|(unit_team, _, health)| Some(**unit_team) == team && !health.is_dead())
+        .all(|(_, status, _)| status.is_done());
+
+    if all_done {
+        next_state.set(state.0.next_phase());
+    }
+}
+
+/// Lets the player end their phase early.
+pub fn end_player_phase_system(
+    keyboard: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut next_state: ResMut<NextState<BattleState>>,
+) {
+    let start_pressed = gamepads.iter().any(|gamepad| {
+        gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::Start))
+    });
+
+    if keyboard.just_pressed(KeyCode::E) || start_pressed {
+        next_state.set(BattleState::EnemyPhase);
+    }
+}
+
+/// Computer-controlled units have no AI to drive them yet, so they hold
+/// their ground and end the phase.
+pub fn pass_computer_phase_system(
+    state: Res<State<BattleState>>,
+    mut units: Query<(&Team, &mut TurnStatus), With<AiProfile>>,
+) {
+    for (team, mut status) in &mut units {
+        if Some(*team) == state.0.team() && !status.is_done() {
+            status.has_acted = true;
+        }
+    }
+}
+
+/// Ends the battle when only one team has units left standing.
+pub fn check_battle_end_system(
+    units: Query<(&Team, &Health), With<Unit>>,
+    mut next_state: ResMut<NextState<BattleState>>,
+    mut commands: Commands,
+) {
+    let mut teams_left = Team::ALL.into_iter().filter(|team| {
+        units
+            .iter()
+            .any(|(unit_team, health)| unit_team == team && !health.is_dead())
+    });
+
+    match (teams_left.next(), teams_left.next()) {
+        (Some(winner), None) => {
+            info!("{winner:?} wins the battle");
+            commands.insert_resource(BattleOutcome { winner });
+            next_state.set(BattleState::BattleEnd);
+        }
+        (None, _) => next_state.set(BattleState::BattleEnd),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world(phase: BattleState) -> World {
+        let mut world = World::new();
+        world.insert_resource(State(phase));
+        world.init_resource::<NextState<BattleState>>();
+        world.init_resource::<TurnCounter>();
+        world.init_resource::<Events<PhaseStarted>>();
+        world
+    }
+
+    fn spawn(world: &mut World, team: Team, hp: u32, status: TurnStatus) -> Entity {
+        world
+            .spawn((Unit, team, Health { current: hp }, status))
+            .id()
+    }
+
+    fn run<M>(world: &mut World, system: impl IntoSystemConfig<M>) {
+        let mut schedule = Schedule::new();
+        schedule.add_system(system);
+        schedule.run(world);
+    }
+
+    fn next_state(world: &World) -> Option<BattleState> {
+        world.resource::<NextState<BattleState>>().0
+    }
+
+    const DONE: TurnStatus = TurnStatus {
+        has_moved: true,
+        has_acted: true,
+    };
+
+    #[test]
+    fn phases_follow_each_other_in_team_order() {
+        let mut phase = BattleState::Deployment;
+        let mut teams = Vec::new();
+        for _ in 0..4 {
+            phase = phase.next_phase();
+            teams.push(phase.team());
+        }
+
+        assert_eq!(
+            teams,
+            [
+                Some(Team::Blue),
+                Some(Team::Red),
+                Some(Team::Green),
+                Some(Team::Blue)
+            ]
+        );
+        assert_eq!(BattleState::Deployment.team(), None);
+        assert_eq!(BattleState::BattleEnd.next_phase(), BattleState::BattleEnd);
+    }
+
+    #[test]
+    fn phase_ends_once_every_living_unit_is_done() {
+        let mut world = world(BattleState::EnemyPhase);
+        spawn(&mut world, Team::Blue, 10, TurnStatus::default());
+        let red = spawn(&mut world, Team::Red, 10, TurnStatus::default());
+        spawn(&mut world, Team::Red, 0, TurnStatus::default());
+
+        run(&mut world, end_phase_when_done_system);
+        assert_eq!(next_state(&world), None);
+
+        *world.get_mut::<TurnStatus>(red).unwrap() = DONE;
+        run(&mut world, end_phase_when_done_system);
+        assert_eq!(next_state(&world), Some(BattleState::OtherFactionPhase));
+    }
+
+    #[test]
+    fn phase_of_a_team_without_units_is_skipped() {
+        let mut world = world(BattleState::OtherFactionPhase);
+        spawn(&mut world, Team::Blue, 10, TurnStatus::default());
+        spawn(&mut world, Team::Red, 10, TurnStatus::default());
+
+        run(&mut world, end_phase_when_done_system);
+        assert_eq!(next_state(&world), Some(BattleState::PlayerPhase));
+    }
+
+    #[test]
+    fn phase_start_readies_only_the_team_whose_phase_it_is() {
+        let mut world = world(BattleState::PlayerPhase);
+        let blue = spawn(&mut world, Team::Blue, 10, DONE);
+        let red = spawn(&mut world, Team::Red, 10, DONE);
+
+        run(&mut world, start_phase_system);
+
+        assert_eq!(world.get::<TurnStatus>(blue), Some(&TurnStatus::default()));
+        assert_eq!(world.get::<TurnStatus>(red), Some(&DONE));
+        assert_eq!(world.resource::<TurnCounter>().turn, 1);
+        assert_eq!(world.resource::<Events<PhaseStarted>>().len(), 1);
+    }
+
+    #[test]
+    fn battle_ends_when_one_team_is_left_standing() {
+        let mut world = world(BattleState::EnemyPhase);
+        spawn(&mut world, Team::Blue, 10, TurnStatus::default());
+        let red = spawn(&mut world, Team::Red, 10, TurnStatus::default());
+
+        run(&mut world, check_battle_end_system);
+        assert_eq!(next_state(&world), None);
+
+        world.get_mut::<Health>(red).unwrap().current = 0;
+        run(&mut world, check_battle_end_system);
+        assert_eq!(next_state(&world), Some(BattleState::BattleEnd));
+        assert_eq!(
+            world.get_resource::<BattleOutcome>(),
+            Some(&BattleOutcome { winner: Team::Blue })
+        );
+    }
+}
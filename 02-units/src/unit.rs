@@ -0,0 +1,256 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    animation::{AnimationState, UnitAnimations},
+    atlas::UnitAtlasCache,
+    battlefield::{Battlefield, UNIT_LAYER},
+    class::ClassDefinition,
+    facing::Facing,
+    grid::GridPos,
+    turn::TurnStatus,
+};
+
+#[derive(Component)]
+pub struct Unit;
+
+/// The side a unit fights for. Each team has its own sprite palette.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Team {
+    Blue,
+    Red,
+    Green,
+}
+
+impl Team {
+    pub const ALL: [Team; 3] = [Team::Blue, Team::Red, Team::Green];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Team::Blue => "Blue",
+            Team::Red => "Red",
+            Team::Green => "Green",
+        }
+    }
+}
+
+/// One per folder in `assets/Sprite Sheets`.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum UnitClass {
+    Archer,
+    Wizard,
+    LanceKnight,
+    SwordFighter,
+    AxeFighter,
+    AxeKnight,
+    SpearFighter,
+    Thief,
+}
+
+impl UnitClass {
+    pub const ALL: [UnitClass; 8] = [
+        UnitClass::Archer,
+        UnitClass::Wizard,
+        UnitClass::LanceKnight,
+        UnitClass::SwordFighter,
+        UnitClass::AxeFighter,
+        UnitClass::AxeKnight,
+        UnitClass::SpearFighter,
+        UnitClass::Thief,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            UnitClass::Archer => "Archer",
+            UnitClass::Wizard => "Wizard",
+            UnitClass::LanceKnight => "LanceKnight",
+            UnitClass::SwordFighter => "SwordFighter",
+            UnitClass::AxeFighter => "AxeFighter",
+            UnitClass::AxeKnight => "AxeKnight",
+            UnitClass::SpearFighter => "SpearFighter",
+            UnitClass::Thief => "Thief",
+        }
+    }
+}
+
+/// A unit's stat line. `hp` is the maximum; the HP it has left is in
+/// `Health`.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Stats {
+    pub hp: u32,
+    pub strength: u32,
+    pub magic: u32,
+    pub defense: u32,
+    pub resistance: u32,
+    pub speed: u32,
+    pub skill: u32,
+    pub luck: u32,
+    pub movement: u32,
+}
+
+impl Stats {
+    /// Stats after `levels` level ups, with growth rates given in percent and
+    /// applied on average rather than rolled.
+    pub fn with_growth(self, growth_rates: &Stats, levels: u32) -> Stats {
+        let grow = |stat: u32, rate: u32| stat + rate * levels / 100;
+
+        Stats {
+            hp: grow(self.hp, growth_rates.hp),
+            strength: grow(self.strength, growth_rates.strength),
+            magic: grow(self.magic, growth_rates.magic),
+            defense: grow(self.defense, growth_rates.defense),
+            resistance: grow(self.resistance, growth_rates.resistance),
+            speed: grow(self.speed, growth_rates.speed),
+            skill: grow(self.skill, growth_rates.skill),
+            luck: grow(self.luck, growth_rates.luck),
+            movement: grow(self.movement, growth_rates.movement),
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Level(pub u32);
+
+/// Marks a unit as computer-controlled, tagged with the temperament a
+/// scenario wants it to have. Units without one are controlled by the
+/// player. There is no AI to act on the tags yet: every computer unit holds
+/// its ground and passes its phase.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum AiProfile {
+    /// Meant to seek out and attack the nearest enemy.
+    Aggressive,
+    /// Meant to attack only enemies that come within reach.
+    Defensive,
+    /// Meant to attack from where it stands, without moving.
+    Stationary,
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Health {
+    pub current: u32,
+}
+
+impl Health {
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+}
+
+#[derive(Bundle)]
+pub struct UnitBundle {
+    pub unit: Unit,
+    pub team: Team,
+    pub class: UnitClass,
+    pub level: Level,
+    pub stats: Stats,
+    pub health: Health,
+    pub grid_pos: GridPos,
+    pub facing: Facing,
+    pub turn_status: TurnStatus,
+    pub animation: AnimationState,
+    #[bundle]
+    pub sprite: SpriteSheetBundle,
+}
+
+impl UnitBundle {
+    /// A unit at full health, standing on `grid_pos`.
+    pub fn new(
+        class: UnitClass,
+        team: Team,
+        level: Level,
+        stats: Stats,
+        grid_pos: GridPos,
+        sprite: UnitSprite,
+        battlefield: &Battlefield,
+    ) -> Self {
+        Self {
+            unit: Unit,
+            team,
+            class,
+            level,
+            stats,
+            health: Health { current: stats.hp },
+            grid_pos,
+            facing: Facing::default(),
+            turn_status: TurnStatus::default(),
+            animation: AnimationState::new(sprite.animations, sprite.columns),
+            sprite: SpriteSheetBundle {
+                texture_atlas: sprite.atlas,
+                sprite: TextureAtlasSprite::new(0),
+                transform: Transform::from_translation(
+                    battlefield.grid_to_world(grid_pos, UNIT_LAYER),
+                ),
+                ..default()
+            },
+        }
+    }
+}
+
+/// The sheet a unit is drawn from and the clips laid out on it.
+pub struct UnitSprite {
+    pub atlas: Handle<TextureAtlas>,
+    pub columns: usize,
+    pub animations: Handle<UnitAnimations>,
+}
+
+impl UnitSprite {
+    /// Slices `image` with the class's sheet layout. Units drawn from the
+    /// same image share its atlas.
+    pub fn new(
+        definition: &ClassDefinition,
+        image: &Handle<Image>,
+        atlas_cache: &mut UnitAtlasCache,
+        texture_atlases: &mut Assets<TextureAtlas>,
+    ) -> Self {
+        Self {
+            atlas: atlas_cache.get_or_load(image, definition.layout, texture_atlases),
+            columns: definition.layout.columns,
+            animations: definition.animations_handle.clone(),
+        }
+    }
+}
+
+/// Finds the living unit standing on a tile. A fallen unit can share its
+/// tile with a living one until it is taken off the battlefield.
+pub fn unit_at<'a>(
+    units: impl IntoIterator<Item = (Entity, &'a GridPos, &'a Health)>,
+    grid_pos: GridPos,
+) -> Option<Entity> {
+    units
+        .into_iter()
+        .find(|(_, unit_pos, health)| **unit_pos == grid_pos && !health.is_dead())
+        .map(|(entity, _, _)| entity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picking_a_tile_skips_the_fallen_unit_on_it() {
+        let fallen = Entity::from_raw(0);
+        let walker = Entity::from_raw(1);
+        let tile = GridPos::new(2, 3);
+        let mut walker_pos = GridPos::new(2, 1);
+        let dead = Health { current: 0 };
+        let alive = Health { current: 10 };
+
+        assert_eq!(
+            unit_at(
+                [(fallen, &tile, &dead), (walker, &walker_pos, &alive)],
+                tile
+            ),
+            None
+        );
+
+        // The walker ends its move on the tile the fallen unit vacated.
+        walker_pos = tile;
+        assert_eq!(
+            unit_at(
+                [(fallen, &tile, &dead), (walker, &walker_pos, &alive)],
+                tile
+            ),
+            Some(walker)
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    animation::{AnimationState, IDLE_CLIP, WALK_CLIP},
+    battlefield::Battlefield,
+    class::{ClassDefinition, ClassDefinitions},
+    combat::{AttackOrder, PendingAttack},
+    facing::Facing,
+    grid::GridPos,
+    movement::MoveRules,
+    picking::TileSelected,
+    selection::{occupants, SelectedUnit},
+    turn::TurnStatus,
+    unit::{Health, Team, UnitClass},
+    weapon::Weapon,
+};
+
+/// Tiles walked per second.
+pub const WALK_SPEED: f32 = 6.0;
+
+/// The tiles a unit still has to walk through, in order.
+#[derive(Component, Debug)]
+pub struct WalkPath {
+    pub from: GridPos,
+    pub steps: VecDeque<GridPos>,
+    /// How far along the step to the next tile the unit is, from 0 to 1.
+    pub progress: f32,
+}
+
+/// Sent once a unit has walked to the end of its path.
+pub struct UnitMoved {
+    pub entity: Entity,
+    pub from: GridPos,
+    pub to: GridPos,
+}
+
+type Walker<'a> = (
+    &'a GridPos,
+    &'a Team,
+    &'a UnitClass,
+    &'a Health,
+    Option<&'a Weapon>,
+    &'a mut TurnStatus,
+    &'a mut AnimationState,
+);
+
+/// Sends the selected unit to the tile the player picked. Picking an enemy
+/// instead sends it to the cheapest tile its weapon reaches the enemy from,
+/// attacking once it gets there. The route is the cheapest one inside the
+/// unit's movement range.
+#[allow(clippy::too_many_arguments)]
+pub fn order_move_system(
+    mut events: EventReader<TileSelected>,
+    selected: Option<Res<SelectedUnit>>,
+    battlefield: Res<Battlefield>,
+    class_definitions: Res<ClassDefinitions>,
+    definitions: Res<Assets<ClassDefinition>>,
+    rules: Res<MoveRules>,
+    mut units: Query<Walker>,
+    mut attacks: EventWriter<AttackOrder>,
+    mut commands: Commands,
+) {
+    let Some(selected) = selected else {
+        return;
+    };
+    let Ok((_, team, ..)) = units.get(selected.entity) else {
+        return;
+    };
+    let team = *team;
+    let start = selected.range.start();
+
+    let Some((destination, target)) = events.iter().find_map(|event| {
+        let Some(target) = event.unit.filter(|target| *target != selected.entity) else {
+            return (event.grid_pos != start && selected.range.can_stop_at(event.grid_pos))
+                .then_some((event.grid_pos, None));
+        };
+        let (_, target_team, _, target_health, ..) = units.get(target).ok()?;
+        if *target_team == team || target_health.is_dead() {
+            return None;
+        }
+        let (.., weapon, _, _) = units.get(selected.entity).ok()?;
+        let destination = selected.range.attack_position(event.grid_pos, weapon?)?;
+
+        Some((destination, Some(target)))
+    }) else {
+        return;
+    };
+
+    if destination == start {
+        if let Some(target) = target {
+            attacks.send(AttackOrder {
+                attacker: selected.entity,
+                defender: target,
+            });
+            commands.remove_resource::<SelectedUnit>();
+        }
+        return;
+    }
+
+    let others = occupants(
+        units
+            .iter()
+            .filter(|(grid_pos, ..)| **grid_pos != start)
+            .map(|(grid_pos, team, _, health, ..)| (grid_pos, team, health)),
+    );
+    let Ok((grid_pos, team, class, .., mut status, mut animation)) = units.get_mut(selected.entity)
+    else {
+        return;
+    };
+    let Some(definition) = class_definitions.get(*class, &definitions) else {
+        return;
+    };
+    let Some(path) = battlefield.find_path(
+        &selected.range,
+        destination,
+        definition.movement_type,
+        *team,
+        &others,
+        *rules,
+    ) else {
+        return;
+    };
+
+    status.has_moved = true;
+    animation.play(WALK_CLIP);
+    let mut unit = commands.entity(selected.entity);
+    unit.insert(WalkPath {
+        from: *grid_pos,
+        steps: path.steps.into_iter().skip(1).collect(),
+        progress: 0.0,
+    });
+    if let Some(target) = target {
+        unit.insert(PendingAttack { target });
+    }
+    commands.remove_resource::<SelectedUnit>();
+}
+
+/// Moves walking units along their path a tile at a time, facing the way
+/// they go, and settles them back into idle at the end.
+pub fn walk_system(
+    time: Res<Time>,
+    battlefield: Res<Battlefield>,
+    mut units: Query<(
+        Entity,
+        &mut WalkPath,
+        &mut GridPos,
+        &mut Transform,
+        &mut Facing,
+        &mut AnimationState,
+    )>,
+    mut moved: EventWriter<UnitMoved>,
+    mut commands: Commands,
+) {
+    for (entity, mut path, mut grid_pos, mut transform, mut facing, mut animation) in &mut units {
+        path.progress += time.delta_seconds() * WALK_SPEED;
+        while path.progress >= 1.0 {
+            let Some(next) = path.steps.pop_front() else {
+                break;
+            };
+            *grid_pos = next;
+            path.progress -= 1.0;
+        }
+
+        let layer = transform.translation.z;
+        let Some(next) = path.steps.front().copied() else {
+            transform.translation = battlefield.grid_to_world(*grid_pos, layer);
+            animation.play(IDLE_CLIP);
+            commands.entity(entity).remove::<WalkPath>();
+            moved.send(UnitMoved {
+                entity,
+                from: path.from,
+                to: *grid_pos,
+            });
+            continue;
+        };
+
+        if let Some(new_facing) = Facing::toward(*grid_pos, next) {
+            facing.set_if_neq(new_facing);
+        }
+        let here = battlefield.grid_to_world(*grid_pos, layer);
+        let there = battlefield.grid_to_world(next, layer);
+        transform.translation = here.lerp(there, path.progress);
+    }
+}
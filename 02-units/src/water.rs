@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+
+use crate::{tilemap::TileType, tileset::Tileset};
+
+#[derive(Component)]
+pub struct WaterTile {
+    first_frame_index: usize,
+}
+
+impl WaterTile {
+    /// Finds the animation of a tile type, if the tileset draws it among its
+    /// still water tiles.
+    pub fn new(tileset: &Tileset, tile_type: TileType) -> Option<Self> {
+        let water = tileset.water.as_ref()?;
+        let position = tileset.position(tile_type)?;
+        let row = position.row.checked_sub(water.first_row)?;
+        if row >= water.rows_per_frame || position.column >= water.columns {
+            return None;
+        }
+
+        Some(Self {
+            first_frame_index: row * water.columns + position.column,
+        })
+    }
+}
+
+/// Shared by every water tile so they all show the same frame.
+#[derive(Resource)]
+pub struct WaterAnimation {
+    pub atlas: Handle<TextureAtlas>,
+    columns: usize,
+    rows_per_frame: usize,
+    frames: usize,
+    timer: Timer,
+    frame: usize,
+}
+
+impl WaterAnimation {
+    pub fn new(
+        tileset: &Tileset,
+        texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+    ) -> Option<Self> {
+        let water = tileset.water.as_ref()?;
+        let water_atlas = TextureAtlas::from_grid(
+            tileset.water_image_handle.clone()?,
+            Vec2::new(tileset.tile_size, tileset.tile_size),
+            water.columns,
+            water.rows_per_frame * water.frames,
+            None,
+            None,
+        );
+
+        Some(Self {
+            atlas: texture_atlases.add(water_atlas),
+            columns: water.columns,
+            rows_per_frame: water.rows_per_frame,
+            frames: water.frames,
+            timer: Timer::from_seconds(water.frame_seconds, TimerMode::Repeating),
+            frame: 0,
+        })
+    }
+
+    fn index(&self, water_tile: &WaterTile) -> usize {
+        self.frame * self.rows_per_frame * self.columns + water_tile.first_frame_index
+    }
+
+    pub fn sprite(&self, water_tile: &WaterTile) -> TextureAtlasSprite {
+        TextureAtlasSprite::new(self.index(water_tile))
+    }
+}
+
+pub fn animate_water_system(
+    time: Res<Time>,
+    mut animation: ResMut<WaterAnimation>,
+    mut water_tiles: Query<(&WaterTile, &mut TextureAtlasSprite)>,
+) {
+    if !animation.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    animation.frame = (animation.frame + 1) % animation.frames;
+    for (water_tile, mut sprite) in &mut water_tiles {
+        sprite.index = animation.index(water_tile);
+    }
+}
@@ -0,0 +1,171 @@
+//! Weapons and the advantage triangle between their types, loaded from an
+//! `*.armory.ron` file.
+
+use std::collections::HashSet;
+
+use bevy::{
+    asset::{AssetLoader, Error, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::terrain::MovementType;
+
+pub const DEFAULT_ARMORY: &str = "Weapons/standard.armory.ron";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum WeaponType {
+    Sword,
+    Lance,
+    Axe,
+    Bow,
+    Tome,
+    Dagger,
+}
+
+impl WeaponType {
+    /// Magic hits with the wielder's magic against resistance instead of
+    /// strength against defense.
+    pub fn is_magic(self) -> bool {
+        self == WeaponType::Tome
+    }
+}
+
+/// The weapon a unit fights with.
+#[derive(Component, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Weapon {
+    pub name: String,
+    pub weapon_type: WeaponType,
+    pub might: u32,
+    pub hit: u32,
+    pub crit: u32,
+    /// Slows down wielders with less strength than this.
+    pub weight: u32,
+    pub min_range: u32,
+    pub max_range: u32,
+    /// Strikes left before the weapon breaks.
+    pub durability: u32,
+    /// Movement types the weapon deals extra damage to.
+    #[serde(default)]
+    pub effective_against: Vec<MovementType>,
+}
+
+impl Weapon {
+    pub fn in_range(&self, distance: u32) -> bool {
+        (self.min_range..=self.max_range).contains(&distance)
+    }
+
+    pub fn is_effective_against(&self, movement_type: MovementType) -> bool {
+        self.effective_against.contains(&movement_type)
+    }
+
+    /// Uses up one strike. Returns true if that broke the weapon.
+    pub fn wear(&mut self) -> bool {
+        self.durability = self.durability.saturating_sub(1);
+        self.durability == 0
+    }
+}
+
+/// Which weapon types beat which, and by how much.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct WeaponTriangle {
+    /// `(stronger, weaker)` pairs.
+    pub advantages: Vec<(WeaponType, WeaponType)>,
+    /// Added to the hit chance of the stronger side and taken from the
+    /// weaker one.
+    pub hit: i32,
+    /// Added to the damage of the stronger side and taken from the weaker
+    /// one.
+    pub damage: i32,
+}
+
+impl WeaponTriangle {
+    /// 1 if `attacking` beats `defending`, -1 if it loses to it, 0 otherwise.
+    pub fn advantage(&self, attacking: WeaponType, defending: WeaponType) -> i32 {
+        if self.advantages.contains(&(attacking, defending)) {
+            1
+        } else if self.advantages.contains(&(defending, attacking)) {
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+/// Every weapon in the game, and the triangle they follow.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "3b9d7e45-1c26-4f80-a5d3-6e04b8f21c97"]
+pub struct Armory {
+    pub triangle: WeaponTriangle,
+    weapons: Vec<Weapon>,
+}
+
+impl Armory {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let armory: Armory = ron::de::from_bytes(bytes)?;
+
+        let mut names = HashSet::new();
+        for weapon in &armory.weapons {
+            if !names.insert(weapon.name.as_str()) {
+                return Err(Error::msg(format!("{} is listed twice", weapon.name)));
+            }
+            if weapon.min_range == 0 || weapon.min_range > weapon.max_range {
+                return Err(Error::msg(format!("{} has an invalid range", weapon.name)));
+            }
+            if weapon.durability == 0 {
+                return Err(Error::msg(format!("{} is already broken", weapon.name)));
+            }
+        }
+
+        Ok(armory)
+    }
+
+    pub fn weapon(&self, name: &str) -> Option<&Weapon> {
+        self.weapons.iter().find(|weapon| weapon.name == name)
+    }
+
+    /// The first listed weapon of the first type a class can wield.
+    pub fn starting_weapon(&self, weapon_types: &[WeaponType]) -> Option<&Weapon> {
+        weapon_types.iter().find_map(|weapon_type| {
+            self.weapons
+                .iter()
+                .find(|weapon| weapon.weapon_type == *weapon_type)
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct ArmoryLoader;
+
+impl AssetLoader for ArmoryLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(Armory::parse(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["armory.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct ActiveArmory(pub Handle<Armory>);
+
+pub fn load_armory_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ActiveArmory(asset_server.load(DEFAULT_ARMORY)));
+}
+
+pub fn armory_loaded(
+    active_armory: Option<Res<ActiveArmory>>,
+    armories: Res<Assets<Armory>>,
+) -> bool {
+    active_armory.is_some_and(|active_armory| armories.contains(&active_armory.0))
+}